@@ -0,0 +1,32 @@
+use tf_demo_parser::demo::{
+    message::MessageType,
+    parser::{gamestateanalyser::GameState, BorrowMessageHandler, MessageHandler},
+};
+use tf_demo_parser::ParserState;
+
+/// An analyser that ignores every message, for parses where nothing reads the game state.
+///
+/// It hands out an empty `GameState`, so the packet loop doesn't have to care which analyser
+/// is in use.
+#[derive(Default)]
+pub(crate) struct NullAnalyser {
+    state: GameState,
+}
+
+impl MessageHandler for NullAnalyser {
+    type Output = GameState;
+
+    fn does_handle(_message_type: MessageType) -> bool {
+        false
+    }
+
+    fn into_output(self, _state: &ParserState) -> Self::Output {
+        self.state
+    }
+}
+
+impl BorrowMessageHandler for NullAnalyser {
+    fn borrow_output(&self, _state: &ParserState) -> &Self::Output {
+        &self.state
+    }
+}
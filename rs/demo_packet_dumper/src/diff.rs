@@ -0,0 +1,220 @@
+//! Field-level comparison of serialised records.
+
+use serde_json::Value;
+
+/// Paths of the values that differ between `old` and `new`, e.g. `players.3.health`.
+///
+/// Keys in `ignored` are skipped wherever they appear. Arrays that changed length are reported
+/// as a whole rather than element by element.
+pub fn changed_paths(old: &Value, new: &Value, ignored: &[&str]) -> Vec<String> {
+    let mut changed = Vec::new();
+    collect_changes(old, new, ignored, &mut String::new(), &mut |path, _| {
+        changed.push(path.to_string());
+    });
+    changed
+}
+
+/// What it takes to turn one record into the next, with the paths as in `changed_paths`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Delta {
+    /// The new value of each path that was added or changed.
+    pub set: Vec<(String, Value)>,
+    /// The object keys that are gone.
+    pub removed: Vec<String>,
+}
+
+impl Delta {
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.removed.is_empty()
+    }
+}
+
+/// The changes from `old` to `new`, for rebuilding `new` from `old`.
+pub fn delta(old: &Value, new: &Value) -> Delta {
+    let mut delta = Delta::default();
+    collect_changes(
+        old,
+        new,
+        &[],
+        &mut String::new(),
+        &mut |path, value| match value {
+            Some(value) => delta.set.push((path.to_string(), value.clone())),
+            None => delta.removed.push(path.to_string()),
+        },
+    );
+    delta
+}
+
+/// Calls `on_change` with the path of each difference, and the value it has in `new` unless it
+/// was removed.
+fn collect_changes(
+    old: &Value,
+    new: &Value,
+    ignored: &[&str],
+    path: &mut String,
+    on_change: &mut dyn FnMut(&str, Option<&Value>),
+) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            for (key, new_value) in new {
+                if ignored.contains(&key.as_str()) {
+                    continue;
+                }
+                let len = path.len();
+                push_segment(path, key);
+                match old.get(key) {
+                    Some(old_value) => {
+                        collect_changes(old_value, new_value, ignored, path, on_change)
+                    }
+                    None => on_change(path, Some(new_value)),
+                }
+                path.truncate(len);
+            }
+            for key in old.keys() {
+                if !new.contains_key(key) && !ignored.contains(&key.as_str()) {
+                    let len = path.len();
+                    push_segment(path, key);
+                    on_change(path, None);
+                    path.truncate(len);
+                }
+            }
+        }
+        (Value::Array(old), Value::Array(new)) if old.len() == new.len() => {
+            for (index, (old_value, new_value)) in old.iter().zip(new).enumerate() {
+                let len = path.len();
+                push_segment(path, &index.to_string());
+                collect_changes(old_value, new_value, ignored, path, on_change);
+                path.truncate(len);
+            }
+        }
+        (old, new) => {
+            if old != new {
+                on_change(path, Some(new));
+            }
+        }
+    }
+}
+
+fn push_segment(path: &mut String, segment: &str) {
+    if !path.is_empty() {
+        path.push('.');
+    }
+    path.push_str(segment);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// The value at `path`, with the missing object keys along it added as `null`.
+    fn at<'v>(value: &'v mut Value, path: &str) -> &'v mut Value {
+        if path.is_empty() {
+            return value;
+        }
+        path.split('.').fold(value, |value, segment| match value {
+            Value::Array(items) => &mut items[segment.parse::<usize>().unwrap()],
+            Value::Object(fields) => fields.entry(segment).or_insert(Value::Null),
+            _ => panic!("'{segment}' of '{path}' is inside a scalar"),
+        })
+    }
+
+    /// Applies `delta` the way a reader rebuilds the next record from the last one.
+    fn apply(value: &mut Value, delta: &Delta) {
+        for (path, new) in &delta.set {
+            *at(value, path) = new.clone();
+        }
+        for path in &delta.removed {
+            let (parent, key) = path.rsplit_once('.').unwrap_or(("", path.as_str()));
+            at(value, parent).as_object_mut().unwrap().remove(key);
+        }
+    }
+
+    fn assert_round_trip(old: Value, new: Value) -> Delta {
+        let delta = delta(&old, &new);
+        let mut rebuilt = old;
+        apply(&mut rebuilt, &delta);
+        assert_eq!(rebuilt, new);
+        delta
+    }
+
+    #[test]
+    fn unchanged_records_have_an_empty_delta() {
+        let record = json!({"tick": 1, "players": [{"health": 125}]});
+        assert!(assert_round_trip(record.clone(), record).is_empty());
+    }
+
+    #[test]
+    fn nested_changes_are_set_by_path() {
+        let delta = assert_round_trip(
+            json!({"tick": 1, "players": [{"health": 125, "class": "scout"}]}),
+            json!({"tick": 2, "players": [{"health": 90, "class": "scout"}]}),
+        );
+        assert_eq!(
+            delta.set,
+            [
+                ("players.0.health".to_string(), json!(90)),
+                ("tick".to_string(), json!(2)),
+            ]
+        );
+        assert!(delta.removed.is_empty());
+    }
+
+    #[test]
+    fn added_and_removed_keys() {
+        let delta = assert_round_trip(
+            json!({"world": {"boundary_min": 0, "gone": true}, "kills": []}),
+            json!({"world": {"boundary_min": 0, "boundary_max": 5}, "kills": []}),
+        );
+        assert_eq!(delta.set, [("world.boundary_max".to_string(), json!(5))]);
+        assert_eq!(delta.removed, ["world.gone"]);
+    }
+
+    #[test]
+    fn arrays_that_change_length_are_set_whole() {
+        let delta = assert_round_trip(
+            json!({"players": [{"health": 1}, {"health": 2}]}),
+            json!({"players": [{"health": 1}, {"health": 2}, {"health": 3}]}),
+        );
+        assert_eq!(delta.set.len(), 1);
+        assert_eq!(delta.set[0].0, "players");
+
+        assert_round_trip(
+            json!({"players": [{"health": 1}, {"health": 2}]}),
+            json!({"players": [{"health": 9}]}),
+        );
+        assert_round_trip(json!({"kills": [1]}), json!({"kills": []}));
+    }
+
+    #[test]
+    fn changed_types_are_set_whole() {
+        assert_round_trip(json!({"info": {"name": "a"}}), json!({"info": null}));
+        assert_round_trip(json!({"info": null}), json!({"info": {"name": "a"}}));
+        assert_round_trip(json!({"players": []}), json!({"players": {}}));
+    }
+
+    #[test]
+    fn a_changed_root_is_set_at_the_empty_path() {
+        let delta = assert_round_trip(json!(1), json!([1, 2]));
+        assert_eq!(delta.set, [(String::new(), json!([1, 2]))]);
+    }
+
+    #[test]
+    fn removed_keys_inside_arrays() {
+        let delta = assert_round_trip(
+            json!({"players": [{"health": 1, "info": {"name": "a"}}]}),
+            json!({"players": [{"health": 1}]}),
+        );
+        assert_eq!(delta.removed, ["players.0.info"]);
+    }
+
+    #[test]
+    fn changed_paths_skip_ignored_keys() {
+        let changed = changed_paths(
+            &json!({"tick": 1, "players": [{"simtime": 1, "health": 2}]}),
+            &json!({"tick": 2, "players": [{"simtime": 2, "health": 3}]}),
+            &["tick", "simtime"],
+        );
+        assert_eq!(changed, ["players.0.health"]);
+    }
+}
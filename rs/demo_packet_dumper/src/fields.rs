@@ -0,0 +1,80 @@
+//! Field selection over serialised records.
+
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+/// Fields kept whatever the selection, so projected records can still be lined up.
+const ALWAYS_KEPT: &[&str] = &["tick"];
+
+/// A whitelist of dot-separated field paths, e.g. `players.health`.
+///
+/// Arrays are transparent: a path applies to every element, so `players.health` keeps the
+/// health of each player. Paths that don't exist in a record are ignored.
+#[derive(Debug, Clone, Default)]
+pub struct FieldSelection {
+    root: Node,
+}
+
+#[derive(Debug, Clone, Default)]
+struct Node {
+    /// The whole value at this path was selected, not just some of its fields.
+    whole: bool,
+    children: BTreeMap<String, Node>,
+}
+
+impl FieldSelection {
+    pub fn new<I, S>(paths: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut selection = FieldSelection::default();
+        for path in paths {
+            let path = path.as_ref().trim();
+            if path.is_empty() {
+                continue;
+            }
+            let mut node = &mut selection.root;
+            for segment in path.split('.') {
+                node = node.children.entry(segment.to_string()).or_default();
+            }
+            node.whole = true;
+        }
+        for &field in ALWAYS_KEPT {
+            selection.root.children.entry(field.to_string()).or_default().whole = true;
+        }
+        selection
+    }
+
+    /// A copy of `value` containing only the selected fields.
+    pub fn project(&self, value: &Value) -> Value {
+        project(&self.root, value).unwrap_or(Value::Null)
+    }
+}
+
+fn project(node: &Node, value: &Value) -> Option<Value> {
+    if node.whole {
+        return Some(value.clone());
+    }
+    match value {
+        Value::Object(fields) => {
+            let projected: Map<String, Value> = node
+                .children
+                .iter()
+                .filter_map(|(key, child)| {
+                    let field = project(child, fields.get(key)?)?;
+                    Some((key.clone(), field))
+                })
+                .collect();
+            Some(Value::Object(projected))
+        }
+        Value::Array(elements) => Some(Value::Array(
+            elements
+                .iter()
+                .filter_map(|element| project(node, element))
+                .collect(),
+        )),
+        // The path goes deeper than the record does
+        _ => None,
+    }
+}
@@ -0,0 +1,178 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::BufRead,
+};
+use tf_demo_parser::demo::{
+    message::Message,
+    packet::Packet,
+    parser::gamestateanalyser::{GameState, Player},
+};
+
+/// The SteamID64 of account id 0 in the public universe.
+const STEAMID64_BASE: u64 = 76_561_197_960_265_728;
+
+/// Restricts GameState output to a set of players, identified by SteamID or in-game name.
+#[derive(Debug, Clone, Default)]
+pub struct PlayerFilter {
+    /// SteamID3 (`[U:1:N]`) of each requested player, and whether it has been seen.
+    steam_ids: HashMap<String, bool>,
+    /// Requested in-game names, and whether they have been seen.
+    names: HashMap<String, bool>,
+}
+
+impl PlayerFilter {
+    /// Builds a filter from SteamID64s, SteamID3s or in-game names.
+    pub fn new<I, S>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        let mut filter = PlayerFilter::default();
+        filter.extend(entries);
+        filter
+    }
+
+    pub fn extend<I, S>(&mut self, entries: I)
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        for entry in entries {
+            let entry = entry.as_ref().trim();
+            if entry.is_empty() {
+                continue;
+            }
+            match to_steamid3(entry) {
+                Some(steam_id) => self.steam_ids.insert(steam_id, false),
+                None => self.names.insert(entry.to_string(), false),
+            };
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.steam_ids.is_empty() && self.names.is_empty()
+    }
+
+    /// How many players were requested.
+    pub fn len(&self) -> usize {
+        self.steam_ids.len() + self.names.len()
+    }
+
+    pub fn matches(&mut self, player: &Player) -> bool {
+        let Some(info) = &player.info else {
+            return false;
+        };
+        let mut matched = false;
+        if let Some(seen) = self.steam_ids.get_mut(&info.steam_id) {
+            *seen = true;
+            matched = true;
+        }
+        if let Some(seen) = self.names.get_mut(&info.name) {
+            *seen = true;
+            matched = true;
+        }
+        matched
+    }
+
+    /// Drops every player that isn't matched by the filter, keeping all global state.
+    pub fn apply(&mut self, state: &mut GameState) {
+        state.players.retain(|player| self.matches(player));
+    }
+
+    /// The requested players that never appeared in the demo.
+    pub fn unmatched(&self) -> impl Iterator<Item = &str> {
+        self.steam_ids
+            .iter()
+            .chain(self.names.iter())
+            .filter(|(_, seen)| !**seen)
+            .map(|(entry, _)| entry.as_str())
+    }
+}
+
+/// Restricts the raw packet output to packets of the requested types, e.g. `DataTables`, or
+/// carrying a message of one, e.g. `GameEvent` or the `SayText2` user message.
+#[derive(Debug, Clone, Default)]
+pub struct PacketFilter {
+    /// The lowercased type names, and whether a packet matched them.
+    types: HashMap<String, bool>,
+}
+
+impl PacketFilter {
+    /// Builds a filter from packet, message or user message type names, in any case.
+    pub fn new<I, S>(entries: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        PacketFilter {
+            types: entries
+                .into_iter()
+                .map(|entry| entry.as_ref().trim().to_lowercase())
+                .filter(|entry| !entry.is_empty())
+                .map(|entry| (entry, false))
+                .collect(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+
+    pub fn matches(&mut self, packet: &Packet<'_>) -> bool {
+        let mut matched = self.check(format!("{:?}", packet.packet_type()));
+        if let Packet::Message(packet) | Packet::Signon(packet) = packet {
+            for message in &packet.messages {
+                matched |= self.check(format!("{:?}", message.get_message_type()));
+                if let Message::UserMessage(user_message) = message {
+                    matched |= self.check(format!("{:?}", user_message.get_message_type()));
+                }
+            }
+        }
+        matched
+    }
+
+    fn check(&mut self, type_name: String) -> bool {
+        match self.types.get_mut(&type_name.to_lowercase()) {
+            Some(seen) => {
+                *seen = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The requested types that no packet matched.
+    pub fn unmatched(&self) -> impl Iterator<Item = &str> {
+        self.types
+            .iter()
+            .filter(|(_, seen)| !**seen)
+            .map(|(entry, _)| entry.as_str())
+    }
+}
+
+/// Reads a list of SteamID64s or SteamID3s, one per line, as SteamID3s. Blank lines and lines
+/// starting with `#` are skipped, anything else that isn't a SteamID is an error naming its line.
+pub fn read_steam_ids(reader: impl BufRead) -> Result<HashSet<String>, String> {
+    let mut steam_ids = HashSet::new();
+    for (index, line) in reader.lines().enumerate() {
+        let line = line.map_err(|e| e.to_string())?;
+        let entry = line.trim();
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+        let steam_id = to_steamid3(entry)
+            .ok_or_else(|| format!("line {}: '{entry}' isn't a SteamID64", index + 1))?;
+        steam_ids.insert(steam_id);
+    }
+    Ok(steam_ids)
+}
+
+/// Normalises a SteamID64 or SteamID3 to the SteamID3 form used by the parser.
+fn to_steamid3(entry: &str) -> Option<String> {
+    if entry.starts_with("[U:") && entry.ends_with(']') {
+        return Some(entry.to_string());
+    }
+    let steam_id: u64 = entry.parse().ok()?;
+    let account_id = steam_id.checked_sub(STEAMID64_BASE)?;
+    Some(format!("[U:1:{account_id}]"))
+}
@@ -0,0 +1,121 @@
+use crate::input::{read_head, DemoBytes};
+use demo_packet_dumper::{read_header, DemoFollower, Error, ParseEvent, ParseOptions, HEADER_SIZE};
+use std::{
+    fs::{self, File},
+    io::{self, Read, Seek, SeekFrom},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::{Duration, Instant},
+};
+use tf_demo_parser::demo::header::Header;
+
+/// How often a followed demo is checked for new data.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Parses a demo that's still being recorded for `--follow`, handing out events as the data for
+/// them is written. Once the file hasn't grown for `idle_timeout` the recording is taken to be
+/// over, and the demo is finished like any other. Returns the data as of then too.
+///
+/// Each time the file grows only the new bytes are read, and the parse carries on from the last
+/// packet that was complete, with the state it had built up so far. An interrupt finishes the
+/// demo as parsed up to then.
+pub fn follow_demo<F>(
+    infile: &str,
+    data: DemoBytes,
+    opts: &ParseOptions,
+    idle_timeout: Duration,
+    interrupted: &AtomicBool,
+    mut on_event: F,
+) -> (Result<Header, Error>, DemoBytes)
+where
+    F: FnMut(ParseEvent<'_, '_>) -> Result<(), Error>,
+{
+    let mut data = match data {
+        DemoBytes::Owned(data) => data,
+        DemoBytes::Mapped(map) => map.to_vec(),
+    };
+    let mut follower = match DemoFollower::new(&data, opts, &mut on_event) {
+        Ok(follower) => follower,
+        Err(e) => return (Err(e), DemoBytes::Owned(data)),
+    };
+    let mut last = false;
+    loop {
+        let chunk = data[follower.resume_at()..].to_vec();
+        match follower.feed(chunk, last, opts, &mut on_event) {
+            Ok(true) => {}
+            Ok(false) => break,
+            Err(e) => return (Err(e), DemoBytes::Owned(data)),
+        }
+        let grown = wait_for_growth(infile, data.len() as u64, idle_timeout, interrupted);
+        if interrupted.load(Ordering::Relaxed) {
+            tracing::info!("Interrupted while following {infile}, finishing it as parsed so far.");
+            break;
+        }
+        // Written up to the last check even when it went idle, that also has to be parsed
+        if let Err(e) = read_appended(infile, &mut data) {
+            tracing::warn!("Couldn't read more of {infile}, finishing it as read so far: {e}");
+            break;
+        }
+        if !grown {
+            tracing::info!("{infile} stopped growing, finishing it.");
+            last = true;
+        }
+    }
+    let header = final_header(infile, &mut data, opts);
+    let result = follower.finish(header, opts, &mut on_event);
+    (result, DemoBytes::Owned(data))
+}
+
+/// Appends whatever was written to the file past the end of `data`.
+fn read_appended(infile: &str, data: &mut Vec<u8>) -> io::Result<()> {
+    let mut file = File::open(infile)?;
+    file.seek(SeekFrom::Start(data.len() as u64))?;
+    file.read_to_end(data)?;
+    Ok(())
+}
+
+/// A recording only fills its header in once it's finished, so it's read again at the end and
+/// replaced in `data` too. A stand-in header is kept, the file has none to read.
+fn final_header(infile: &str, data: &mut [u8], opts: &ParseOptions) -> Option<Header> {
+    if opts.header.is_some() || data.len() < HEADER_SIZE {
+        return None;
+    }
+    let head = match read_head(infile) {
+        Ok(head) if head.len() == HEADER_SIZE => head,
+        Ok(_) => return None,
+        Err(e) => {
+            tracing::warn!("Couldn't read the header of {infile} again: {e}");
+            return None;
+        }
+    };
+    match read_header(&head) {
+        Ok(header) => {
+            data[..HEADER_SIZE].copy_from_slice(&head);
+            Some(header)
+        }
+        Err(e) => {
+            tracing::warn!("The header of {infile} no longer reads, keeping the first one: {e}");
+            None
+        }
+    }
+}
+
+/// Waits until the file is larger than `len`, returning `false` if it didn't grow for
+/// `idle_timeout` or the run was interrupted.
+fn wait_for_growth(
+    infile: &str,
+    len: u64,
+    idle_timeout: Duration,
+    interrupted: &AtomicBool,
+) -> bool {
+    let waiting_since = Instant::now();
+    while waiting_since.elapsed() < idle_timeout && !interrupted.load(Ordering::Relaxed) {
+        thread::sleep(POLL_INTERVAL);
+        match fs::metadata(infile) {
+            Ok(metadata) if metadata.len() > len => return true,
+            Ok(_) => {}
+            Err(e) => tracing::warn!("Couldn't check whether {infile} grew: {e}"),
+        }
+    }
+    false
+}
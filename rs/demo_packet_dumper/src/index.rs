@@ -0,0 +1,36 @@
+use serde::Serialize;
+
+/// Where one tick's packets start in the demo.
+#[derive(Debug, Serialize)]
+pub struct TickOffset {
+    pub tick: u32,
+    /// From the start of the file, header included.
+    pub bit_offset: usize,
+    /// The latest tick at or before this one with a full entity update, `None` before the first.
+    /// Entities are sent as deltas, so resuming at `bit_offset` needs the state as of this tick.
+    pub keyframe_tick: Option<u32>,
+}
+
+/// Maps ticks to their position in the packet stream, for seeking in a viewer.
+///
+/// Resuming from an offset also needs the data tables and string tables from the signon packets
+/// at the start of the demo, which every seek has to parse first.
+#[derive(Debug, Default, Serialize)]
+pub struct OffsetIndex {
+    /// The ticks with a full entity update, which seeking can start the state from.
+    pub keyframes: Vec<u32>,
+    pub ticks: Vec<TickOffset>,
+}
+
+impl OffsetIndex {
+    pub fn tick(&mut self, tick: u32, bit_offset: usize, full_update: bool) {
+        if full_update {
+            self.keyframes.push(tick);
+        }
+        self.ticks.push(TickOffset {
+            tick,
+            bit_offset,
+            keyframe_tick: self.keyframes.last().copied(),
+        });
+    }
+}
@@ -0,0 +1,136 @@
+use demo_packet_dumper::HEADER_SIZE;
+use memmap2::Mmap;
+use std::{
+    ffi::OsStr,
+    fs::{self, File},
+    io::{self, Read},
+    ops::Deref,
+    path::Path,
+};
+use zip::ZipArchive;
+
+/// The most memory reserved up front for a demo read from an archive. The size comes from the
+/// archive's own bookkeeping, which a broken or crafted archive can make up, so anything larger
+/// grows as it's actually decompressed.
+const MAX_PREALLOCATION: u64 = 256 << 20;
+
+/// The bytes of a demo, either read into memory or mapped from the file.
+pub enum DemoBytes {
+    Owned(Vec<u8>),
+    Mapped(Mmap),
+}
+
+impl Deref for DemoBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            DemoBytes::Owned(bytes) => bytes,
+            DemoBytes::Mapped(map) => map,
+        }
+    }
+}
+
+/// A demo stored in a zip archive, given as `match.zip:round1.dem`, or just `match.zip` for an
+/// archive holding a single demo.
+pub struct ArchivedDemo<'a> {
+    pub archive: &'a str,
+    pub member: Option<&'a str>,
+}
+
+impl<'a> ArchivedDemo<'a> {
+    /// `None` if `infile` isn't in an archive.
+    pub fn parse(infile: &'a str) -> Option<Self> {
+        const ZIP: &str = ".zip";
+        let lower = infile.to_ascii_lowercase();
+        if let Some(end) = lower.find(".zip:").map(|start| start + ZIP.len()) {
+            return Some(ArchivedDemo {
+                archive: &infile[..end],
+                member: Some(&infile[end + 1..]),
+            });
+        }
+        lower.ends_with(ZIP).then_some(ArchivedDemo {
+            archive: infile,
+            member: None,
+        })
+    }
+
+    /// Decompresses the demo, or only its first `limit` bytes.
+    fn read(&self, limit: Option<u64>) -> io::Result<Vec<u8>> {
+        let mut archive = ZipArchive::new(File::open(self.archive)?).map_err(io::Error::other)?;
+        let name = self.member_name(&archive)?;
+        let entry = archive.by_name(&name).map_err(io::Error::other)?;
+        let size = limit.map_or(entry.size(), |limit| entry.size().min(limit));
+        let mut demo = Vec::with_capacity(size.min(MAX_PREALLOCATION) as usize);
+        entry.take(size).read_to_end(&mut demo)?;
+        Ok(demo)
+    }
+
+    /// The full name of the demo in the archive. A member given without its directory is found
+    /// by its file name, and without a member the archive has to hold exactly one demo.
+    fn member_name(&self, archive: &ZipArchive<File>) -> io::Result<String> {
+        let is_match = |name: &&str| match self.member {
+            Some(member) => {
+                *name == member || Path::new(name).file_name() == Some(OsStr::new(member))
+            }
+            None => name.to_ascii_lowercase().ends_with(".dem"),
+        };
+        let mut matches = archive.file_names().filter(is_match);
+        match (matches.next(), matches.next(), self.member) {
+            (Some(name), None, _) => Ok(name.to_string()),
+            (None, _, Some(member)) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no '{member}' in it", self.archive),
+            )),
+            (None, _, None) => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("{} has no demo in it", self.archive),
+            )),
+            (Some(_), Some(_), _) => Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "{} has more than one match, pick one as {}:<name>.dem",
+                    self.archive, self.archive
+                ),
+            )),
+        }
+    }
+}
+
+/// Loads a whole demo, from stdin if `infile` is `-`.
+///
+/// With `mmap` the file is mapped instead of read, stdin and archives are always read.
+pub fn read_demo(infile: &str, mmap: bool) -> io::Result<DemoBytes> {
+    tracing::info!("Reading provided input demo {infile}...");
+    if let Some(archived) = ArchivedDemo::parse(infile) {
+        // The demo borrows its input, so it's decompressed into memory as a whole
+        return archived.read(None).map(DemoBytes::Owned);
+    }
+    if infile == "-" {
+        // The demo borrows its input, so the whole stream has to be buffered anyway
+        let mut file = Vec::new();
+        io::stdin().lock().read_to_end(&mut file)?;
+        return Ok(DemoBytes::Owned(file));
+    }
+    if !mmap {
+        return fs::read(infile).map(DemoBytes::Owned);
+    }
+    let file = File::open(infile)?;
+    // SAFETY: the map is only ever read, but if another process truncates the file while it's
+    // mapped, reading the missing pages raises SIGBUS. That's the documented cost of --mmap,
+    // demos aren't expected to change while they're being parsed.
+    let map = unsafe { Mmap::map(&file)? };
+    Ok(DemoBytes::Mapped(map))
+}
+
+/// Reads only as much of a demo file as its header takes up.
+pub fn read_head(infile: &str) -> io::Result<Vec<u8>> {
+    if let Some(archived) = ArchivedDemo::parse(infile) {
+        return archived.read(Some(HEADER_SIZE as u64));
+    }
+    let mut head = Vec::with_capacity(HEADER_SIZE);
+    File::open(infile)?
+        .take(HEADER_SIZE as u64)
+        .read_to_end(&mut head)?;
+    Ok(head)
+}
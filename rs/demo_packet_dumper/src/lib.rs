@@ -0,0 +1,665 @@
+pub mod diff;
+pub mod fields;
+pub mod filter;
+pub mod schema;
+pub mod stats;
+pub mod streams;
+
+mod analyser;
+
+use analyser::NullAnalyser;
+use bitbuffer::{BitRead, BitReadBuffer, LittleEndian};
+use stats::{PacketCounter, PacketCounts};
+use std::{
+    fmt,
+    ops::RangeInclusive,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+use streams::{Extractors, StreamKind, StreamOptions, StreamRecord};
+use tf_demo_parser::demo::{
+    header::Header,
+    message::{Message, ServerInfoMessage},
+    packet::Packet,
+    parser::{
+        gamestateanalyser::{GameState, GameStateAnalyser},
+        BorrowMessageHandler, DemoHandler, MessageHandler, RawPacketStream,
+    },
+};
+use tf_demo_parser::{Demo, ParseError, Stream};
+
+/// Controls what `parse_demo` collects while walking the packet stream.
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    pub parse_raw: bool,
+    /// Hand out the GameState after every tick. The state is tracked regardless when a side
+    /// stream needs it.
+    pub parse_gamestate: bool,
+    /// Inclusive window of ticks to produce output for, side stream records included. Packets
+    /// outside of it are still handled, and the side streams still see them.
+    pub tick_range: Option<(u32, u32)>,
+    /// Stop parsing once the server tick passes this.
+    pub max_ticks: Option<u32>,
+    /// Side streams to extract alongside the GameState deltas.
+    pub streams: Vec<StreamKind>,
+    pub stream_options: StreamOptions,
+    /// How many packets in a row may fail before the stream is considered out of sync.
+    pub max_consecutive_errors: u32,
+    /// How many packet errors in total are recovered from before giving up on the demo.
+    pub max_total_errors: Option<u32>,
+    /// Checked between packets, once any is set parsing stops as if the demo had ended there.
+    pub stop: Vec<Arc<AtomicBool>>,
+    /// Use this header instead of reading one, for packet data cut out of a demo. The packet
+    /// stream then starts at the start of the data.
+    pub header: Option<Header>,
+    /// Count the packet, message and user message types, sent as `ParseEvent::PacketStats`.
+    pub packet_stats: bool,
+    /// Parse demos from a protocol outside `SUPPORTED_PROTOCOLS` anyway, rather than failing with
+    /// `Error::UnsupportedProtocol` before the first packet.
+    pub protocol_compat: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            parse_raw: false,
+            parse_gamestate: true,
+            tick_range: None,
+            max_ticks: None,
+            streams: Vec::new(),
+            stream_options: StreamOptions::default(),
+            max_consecutive_errors: 100,
+            max_total_errors: None,
+            stop: Vec::new(),
+            header: None,
+            packet_stats: false,
+            protocol_compat: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Whether anything reads the game state, which otherwise isn't worth reconstructing.
+    fn tracks_state(&self) -> bool {
+        self.parse_gamestate || !self.streams.is_empty()
+    }
+
+    fn stopped(&self) -> bool {
+        self.stop.iter().any(|stop| stop.load(Ordering::Relaxed))
+    }
+
+    fn in_window(&self, tick: u32) -> bool {
+        self.tick_range
+            .is_none_or(|(start, end)| (start..=end).contains(&tick))
+    }
+}
+
+/// Everything `parse_demo` collected from a demo.
+#[derive(Debug)]
+pub struct DemoOutput<'a> {
+    pub header: Header,
+    pub gamestates: Vec<GameState>,
+    pub raw_packets: Vec<Packet<'a>>,
+    pub records: Vec<StreamRecord>,
+    /// Packet errors that were recovered from, with the tick they occurred at.
+    pub packet_errors: Vec<(u32, ParseError)>,
+}
+
+/// Keeps GameState frames in strictly increasing tick order. Resyncing after packet errors can
+/// hand out a tick again, or one from before it, which consumers of the frames don't expect.
+#[derive(Debug, Default)]
+pub struct TickOrder {
+    last: Option<u32>,
+}
+
+impl TickOrder {
+    /// Takes `tick` as the latest if it comes after the previous one. Otherwise returns the
+    /// previous tick, and the frame at `tick` should be skipped.
+    pub fn advance(&mut self, tick: u32) -> Result<(), u32> {
+        match self.last {
+            Some(previous) if tick <= previous => Err(previous),
+            _ => {
+                self.last = Some(tick);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// An item handed to the `parse_demo_with` callback, or the `parse_demo` observer, as soon as it
+/// is produced.
+pub enum ParseEvent<'a, 'b> {
+    Header(&'b Header),
+    /// The server tick advanced, with how far into the demo the packet stream is.
+    Tick {
+        tick: u32,
+        bytes_read: u64,
+        /// Where in the demo the packet that advanced the tick started.
+        bit_position: usize,
+        /// That packet carried entities in full rather than as a delta, so the state can be
+        /// rebuilt from this tick on without the packets before it.
+        full_update: bool,
+    },
+    /// The server's description of itself, sent as the client connects.
+    ServerInfo(&'b ServerInfoMessage),
+    RawPacket(&'b Packet<'a>),
+    GameState(&'b GameState),
+    Record(StreamRecord),
+    /// A packet failed to parse, parsing continues from the next one.
+    PacketError {
+        tick: u32,
+        error: ParseError,
+        /// Where in the demo the failed packet started.
+        bit_position: usize,
+        /// How far the stream moved past the failed packet before picking up again.
+        skipped_bits: usize,
+    },
+    /// How many of each packet type the demo had, with `ParseOptions::packet_stats`. Sent just
+    /// before `Done`.
+    PacketStats(PacketCounts),
+    /// The parse finished, after every other event. Not sent if it failed or was aborted.
+    Done {
+        /// How many packets parsed, which the header counts as `frames`.
+        frames: u64,
+    },
+}
+
+/// The `demo_type` every valid source engine demo header starts with.
+const DEMO_MAGIC: &str = "HL2DEMO";
+
+/// The size of the demo header, everything after it is the packet stream.
+pub const HEADER_SIZE: usize = 1072;
+
+/// The demo file format every TF2 demo has been written in.
+const DEMO_VERSION: u32 = 3;
+
+/// How close to the end of a demo that's still being written a packet error is taken to be the
+/// packet that's still being written, rather than a broken one. Well above the largest packets.
+const PARTIAL_TAIL_BITS: usize = 8 << 20;
+
+/// The network protocols the packets are known to parse for, from the first TF2 releases up to
+/// the current one.
+pub const SUPPORTED_PROTOCOLS: RangeInclusive<u32> = 7..=24;
+
+#[derive(Debug)]
+pub enum Error {
+    Header(bitbuffer::BitError),
+    /// The header was readable but doesn't describe a demo.
+    InvalidDemoType(String),
+    /// The demo was recorded with a format or network protocol the parser doesn't know, which
+    /// would otherwise show up as a flood of packet errors.
+    UnsupportedProtocol { version: u32, protocol: u32 },
+    Packet(ParseError),
+    /// Recovering from packet errors stopped making progress through the demo.
+    LostSync { tick: u32, consecutive_errors: u32 },
+    /// More packets failed than `ParseOptions::max_total_errors` allows.
+    TooManyErrors { tick: u32, errors: u32 },
+    /// An error raised by the consumer of the parsed output.
+    Output(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    pub fn output<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> Self {
+        Error::Output(e.into())
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Header(e) => write!(
+                f,
+                "File is not a valid TF2 demo or header is truncated: {e}"
+            ),
+            Error::InvalidDemoType(demo_type) => write!(
+                f,
+                "File is not a valid TF2 demo: expected demo type '{DEMO_MAGIC}', found '{demo_type}'"
+            ),
+            Error::UnsupportedProtocol { version, protocol } => write!(
+                f,
+                "Unsupported demo protocol version {protocol} (demo format {version}), known are {} to {}",
+                SUPPORTED_PROTOCOLS.start(),
+                SUPPORTED_PROTOCOLS.end()
+            ),
+            Error::Packet(e) => write!(f, "Couldn't handle packet: {e}"),
+            Error::LostSync {
+                tick,
+                consecutive_errors,
+            } => write!(
+                f,
+                "Lost demo sync at tick {tick} after {consecutive_errors} consecutive packet errors"
+            ),
+            Error::TooManyErrors { tick, errors } => write!(
+                f,
+                "Gave up at tick {tick} after {errors} packet errors, the demo is too damaged"
+            ),
+            Error::Output(e) => write!(f, "Couldn't write output: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+/// Reads just the header of a demo, which only needs the first `HEADER_SIZE` bytes.
+pub fn read_header(data: &[u8]) -> Result<Header, Error> {
+    let demo = Demo::new(data);
+    read_valid_header(&mut demo.get_stream())
+}
+
+fn read_valid_header(stream: &mut Stream<'_>) -> Result<Header, Error> {
+    let header = Header::read(stream).map_err(Error::Header)?;
+    // Bail before trying to make sense of packets from something that isn't a demo
+    if header.demo_type.trim_end_matches('\0') != DEMO_MAGIC {
+        return Err(Error::InvalidDemoType(header.demo_type));
+    }
+    Ok(header)
+}
+
+/// Checks that the demo's packets are in a format the parser knows.
+pub fn check_protocol(header: &Header) -> Result<(), Error> {
+    if header.version == DEMO_VERSION && SUPPORTED_PROTOCOLS.contains(&header.protocol) {
+        Ok(())
+    } else {
+        Err(Error::UnsupportedProtocol {
+            version: header.version,
+            protocol: header.protocol,
+        })
+    }
+}
+
+/// A header for packet data that lost its own, with nothing known but the map.
+pub fn stand_in_header(map: &str) -> Header {
+    Header {
+        demo_type: DEMO_MAGIC.to_string(),
+        version: DEMO_VERSION,
+        protocol: *SUPPORTED_PROTOCOLS.end(),
+        server: String::new(),
+        nick: String::new(),
+        map: map.to_string(),
+        game: "tf".to_string(),
+        duration: 0.0,
+        ticks: 0,
+        frames: 0,
+        signon: 0,
+    }
+}
+
+/// Parses a demo, collecting the GameState snapshots and optionally the raw packets in memory.
+///
+/// `observer` sees every event before it's collected, e.g. to drive a progress display.
+pub fn parse_demo<'a>(
+    data: &'a [u8],
+    opts: ParseOptions,
+    mut observer: Option<&mut dyn FnMut(&ParseEvent<'a, '_>)>,
+) -> Result<DemoOutput<'a>, Error> {
+    let mut gamestates = Vec::new();
+    let mut raw_packets = Vec::new();
+    let mut records = Vec::new();
+    let mut packet_errors = Vec::new();
+    let header = parse_demo_with(data, &opts, |event| {
+        if let Some(observer) = observer.as_mut() {
+            observer(&event);
+        }
+        match event {
+            ParseEvent::RawPacket(packet) => raw_packets.push(packet.clone()),
+            ParseEvent::GameState(state) => gamestates.push(state.clone()),
+            ParseEvent::Record(record) => records.push(record),
+            ParseEvent::PacketError { tick, error, .. } => packet_errors.push((tick, error)),
+            ParseEvent::Header(_)
+            | ParseEvent::Tick { .. }
+            | ParseEvent::ServerInfo(_)
+            | ParseEvent::PacketStats(_)
+            | ParseEvent::Done { .. } => {}
+        }
+        Ok(())
+    })?;
+    Ok(DemoOutput {
+        header,
+        gamestates,
+        raw_packets,
+        records,
+        packet_errors,
+    })
+}
+
+/// Parses a demo, handing each produced item to `on_event` instead of collecting it.
+///
+/// Any error returned from `on_event` aborts the parse.
+pub fn parse_demo_with<'a, F>(
+    data: &'a [u8],
+    opts: &ParseOptions,
+    mut on_event: F,
+) -> Result<Header, Error>
+where
+    F: FnMut(ParseEvent<'a, '_>) -> Result<(), Error>,
+{
+    let demo = Demo::new(data);
+    let mut stream = demo.get_stream();
+    let header = match &opts.header {
+        Some(header) => header.clone(),
+        None => read_valid_header(&mut stream)?,
+    };
+    start_parse(&header, opts, &mut on_event)?;
+
+    let frames = if opts.tracks_state() {
+        let mut parser = PacketParser::new(GameStateAnalyser::new(), &header, opts);
+        parser.run(stream, 0, false, opts, &mut on_event)?;
+        parser.finish(opts, &mut on_event)?
+    } else {
+        // Nothing reads the state, so don't pay for reconstructing it
+        let mut parser = PacketParser::new(NullAnalyser::default(), &header, opts);
+        parser.run(stream, 0, false, opts, &mut on_event)?;
+        parser.finish(opts, &mut on_event)?
+    };
+    on_event(ParseEvent::Done { frames })?;
+    Ok(header)
+}
+
+/// Checks the header's protocol, unless it's a stand-in, and hands the header out.
+fn start_parse<'a, F>(header: &Header, opts: &ParseOptions, on_event: &mut F) -> Result<(), Error>
+where
+    F: FnMut(ParseEvent<'a, '_>) -> Result<(), Error>,
+{
+    // A stand-in header is made up, there's nothing to check
+    if opts.header.is_none() && !opts.protocol_compat {
+        check_protocol(header)?;
+    }
+    on_event(ParseEvent::Header(header))
+}
+
+/// Parses a demo that's still being recorded, as its data comes in.
+///
+/// `parse_demo_with` borrows the whole demo, which a recording doesn't have yet. This takes the
+/// packet stream in chunks instead, and keeps the parser's state between them, so each chunk
+/// only has to start where the last one ran out: at the first packet that wasn't fully written.
+pub struct DemoFollower {
+    header: Header,
+    parser: FollowedParser,
+    /// In bytes from the start of the demo. Packets are framed in whole bytes, so they always
+    /// start on one.
+    resume_at: usize,
+    /// The parse stopped for good, on a stop flag or `ParseOptions::max_ticks`.
+    ended: bool,
+}
+
+/// The chunks own their data, so what the parser keeps of their packets lives as long as it.
+enum FollowedParser {
+    State(PacketParser<'static, GameStateAnalyser>),
+    Null(PacketParser<'static, NullAnalyser>),
+}
+
+impl DemoFollower {
+    /// Starts following a demo from its start, which has to hold the whole header unless
+    /// `ParseOptions::header` stands in for it.
+    pub fn new<F>(start: &[u8], opts: &ParseOptions, mut on_event: F) -> Result<Self, Error>
+    where
+        F: FnMut(ParseEvent<'static, '_>) -> Result<(), Error>,
+    {
+        let (header, resume_at) = match &opts.header {
+            Some(header) => (header.clone(), 0),
+            None => (read_header(start)?, HEADER_SIZE),
+        };
+        start_parse(&header, opts, &mut on_event)?;
+        let parser = if opts.tracks_state() {
+            FollowedParser::State(PacketParser::new(GameStateAnalyser::new(), &header, opts))
+        } else {
+            FollowedParser::Null(PacketParser::new(NullAnalyser::default(), &header, opts))
+        };
+        Ok(DemoFollower {
+            header,
+            parser,
+            resume_at,
+            ended: false,
+        })
+    }
+
+    /// Where in the demo, in bytes, the next chunk has to start.
+    pub fn resume_at(&self) -> usize {
+        self.resume_at
+    }
+
+    /// Parses the demo's data from `resume_at` up to as far as it's been written. With `last`
+    /// nothing more is coming, so the data running out is the end of the demo. Returns whether
+    /// the parse wants more data, `false` once it stopped for good.
+    pub fn feed<F>(
+        &mut self,
+        chunk: Vec<u8>,
+        last: bool,
+        opts: &ParseOptions,
+        mut on_event: F,
+    ) -> Result<bool, Error>
+    where
+        F: FnMut(ParseEvent<'static, '_>) -> Result<(), Error>,
+    {
+        if self.ended {
+            return Ok(false);
+        }
+        let stream = Stream::new(BitReadBuffer::new_owned(chunk, LittleEndian));
+        let offset_bits = self.resume_at * 8;
+        let partial = !last;
+        let resume = match &mut self.parser {
+            FollowedParser::State(parser) => {
+                parser.run(stream, offset_bits, partial, opts, &mut on_event)?
+            }
+            FollowedParser::Null(parser) => {
+                parser.run(stream, offset_bits, partial, opts, &mut on_event)?
+            }
+        };
+        match resume {
+            Some(resume_bits) => {
+                self.resume_at = resume_bits / 8;
+                Ok(true)
+            }
+            None => {
+                self.ended = true;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Finishes the side streams and sends `Done`, returning the header. `header` replaces the
+    /// one the demo started with, as a recording only fills its header in once it's done.
+    pub fn finish<F>(
+        self,
+        header: Option<Header>,
+        opts: &ParseOptions,
+        mut on_event: F,
+    ) -> Result<Header, Error>
+    where
+        F: FnMut(ParseEvent<'static, '_>) -> Result<(), Error>,
+    {
+        let frames = match self.parser {
+            FollowedParser::State(parser) => parser.finish(opts, &mut on_event)?,
+            FollowedParser::Null(parser) => parser.finish(opts, &mut on_event)?,
+        };
+        on_event(ParseEvent::Done { frames })?;
+        Ok(header.unwrap_or(self.header))
+    }
+}
+
+/// The state of the packet loop, which outlives a single stream so a demo that's still being
+/// written can be carried on with where its data ran out.
+struct PacketParser<'a, A: MessageHandler> {
+    handler: DemoHandler<'a, A>,
+    extractors: Extractors,
+    current_tick: u32,
+    consecutive_errors: u32,
+    total_errors: u32,
+    frames: u64,
+    counter: Option<PacketCounter>,
+}
+
+impl<'a, A> PacketParser<'a, A>
+where
+    A: MessageHandler<Output = GameState> + BorrowMessageHandler,
+{
+    fn new(analyser: A, header: &Header, opts: &ParseOptions) -> Self {
+        let mut handler = DemoHandler::with_analyser(analyser);
+        handler.handle_header(header);
+        PacketParser {
+            handler,
+            extractors: Extractors::new(&opts.streams, &opts.stream_options),
+            current_tick: 0,
+            consecutive_errors: 0,
+            total_errors: 0,
+            frames: 0,
+            counter: opts.packet_stats.then(PacketCounter::default),
+        }
+    }
+
+    /// Walks the packets of `stream`, which starts `offset_bits` into the demo, handing
+    /// everything produced to `on_event`. With `partial` the demo is still being written, so
+    /// running out of data isn't its end: that returns where the incomplete packet starts, to
+    /// carry on from once there's more. Otherwise returns `None` once the parse is over.
+    fn run<F>(
+        &mut self,
+        stream: Stream<'a>,
+        offset_bits: usize,
+        partial: bool,
+        opts: &ParseOptions,
+        on_event: &mut F,
+    ) -> Result<Option<usize>, Error>
+    where
+        F: FnMut(ParseEvent<'a, '_>) -> Result<(), Error>,
+    {
+        let data_bits = stream.bit_len();
+        let mut packet_stream: RawPacketStream = RawPacketStream::new(stream);
+        while !opts.stopped() {
+            let stream_position = packet_stream.pos();
+            let bit_position = offset_bits + stream_position;
+            match packet_stream.next(&self.handler.state_handler) {
+                Ok(Some(packet)) => {
+                    self.consecutive_errors = 0;
+                    self.frames += 1;
+                    if let Some(counter) = self.counter.as_mut() {
+                        counter.count(&packet);
+                    }
+                    // Packets outside the window are still handled so the state is correct once
+                    // it opens.
+                    if opts.parse_raw && opts.in_window(self.handler.server_tick.into()) {
+                        on_event(ParseEvent::RawPacket(&packet))?;
+                    }
+                    if let Some(info) = server_info(&packet) {
+                        on_event(ParseEvent::ServerInfo(info))?;
+                    }
+                    self.extractors
+                        .on_packet(self.current_tick, &packet, self.handler.borrow_output());
+                    let full_update = is_full_update(&packet);
+
+                    self.handler.handle_packet(packet).map_err(Error::Packet)?;
+
+                    if self.handler.server_tick != self.current_tick {
+                        let tick: u32 = self.handler.server_tick.into();
+                        if opts.max_ticks.is_some_and(|max_ticks| tick > max_ticks) {
+                            return Ok(None);
+                        }
+                        on_event(ParseEvent::Tick {
+                            tick,
+                            bytes_read: ((offset_bits + packet_stream.pos()) / 8) as u64,
+                            bit_position,
+                            full_update,
+                        })?;
+                        if opts.parse_gamestate && opts.in_window(tick) {
+                            on_event(ParseEvent::GameState(self.handler.borrow_output()))?;
+                        }
+                        self.extractors.on_tick(tick, self.handler.borrow_output());
+                    }
+                    self.current_tick = self.handler.server_tick.into();
+                    // Drained either way, records from before the window opens are dropped
+                    let in_window = opts.in_window(self.current_tick);
+                    for record in self.extractors.drain() {
+                        if in_window {
+                            on_event(ParseEvent::Record(record))?;
+                        }
+                    }
+                }
+                Ok(None) if partial => return Ok(Some(bit_position)),
+                Ok(None) => return Ok(None),
+                // Reported by the parse once the packet is complete, or the demo finished without
+                // it
+                Err(_)
+                    if partial
+                        && data_bits.saturating_sub(stream_position) < PARTIAL_TAIL_BITS =>
+                {
+                    return Ok(Some(bit_position))
+                }
+                Err(e) => {
+                    // We want to pull as much data as possible, even if this packet is corrupted
+                    // Continue the stream and see if we can't recover.
+                    let skipped_bits = packet_stream.pos().saturating_sub(stream_position);
+                    on_event(ParseEvent::PacketError {
+                        tick: self.current_tick,
+                        error: e,
+                        bit_position,
+                        skipped_bits,
+                    })?;
+                    self.consecutive_errors += 1;
+                    self.total_errors += 1;
+                    // Without moving forward, retrying would just fail on the same bits forever
+                    if skipped_bits == 0 || self.consecutive_errors >= opts.max_consecutive_errors
+                    {
+                        return Err(Error::LostSync {
+                            tick: self.current_tick,
+                            consecutive_errors: self.consecutive_errors,
+                        });
+                    }
+                    if opts.max_total_errors.is_some_and(|max| self.total_errors > max) {
+                        return Err(Error::TooManyErrors {
+                            tick: self.current_tick,
+                            errors: self.total_errors,
+                        });
+                    }
+                    packet_stream.ended = false;
+                    packet_stream.incomplete = false;
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Finishes the side streams after the last packet, returning how many packets parsed,
+    /// which the header counts as `frames`.
+    fn finish<F>(mut self, opts: &ParseOptions, on_event: &mut F) -> Result<u64, Error>
+    where
+        F: FnMut(ParseEvent<'a, '_>) -> Result<(), Error>,
+    {
+        self.extractors
+            .finish(self.current_tick, self.handler.borrow_output());
+        // Stamped as of the last tick like the records drained above, except for the documents,
+        // which sum up the whole demo
+        let in_window = opts.in_window(self.current_tick);
+        for record in self.extractors.drain() {
+            if in_window || record.kind().is_document() {
+                on_event(ParseEvent::Record(record))?;
+            }
+        }
+        if let Some(counter) = self.counter.take() {
+            on_event(ParseEvent::PacketStats(counter.finish()))?;
+        }
+        Ok(self.frames)
+    }
+}
+
+fn server_info<'p>(packet: &'p Packet<'_>) -> Option<&'p ServerInfoMessage> {
+    let (Packet::Message(packet) | Packet::Signon(packet)) = packet else {
+        return None;
+    };
+    packet.messages.iter().find_map(|message| match message {
+        Message::ServerInfo(info) => Some(&**info),
+        _ => None,
+    })
+}
+
+/// Whether a packet carries entities in full, as sent on connecting and after the server lost
+/// track of what the client has.
+fn is_full_update(packet: &Packet<'_>) -> bool {
+    let (Packet::Message(packet) | Packet::Signon(packet)) = packet else {
+        return false;
+    };
+    packet.messages.iter().any(|message| match message {
+        Message::PacketEntities(entities) => entities.delta.is_none(),
+        _ => false,
+    })
+}
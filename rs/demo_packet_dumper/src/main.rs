@@ -1,140 +1,2228 @@
-use bitbuffer::BitRead;
 use clap::{ArgAction, Parser};
-use indicatif::{ProgressBar, ProgressState, ProgressStyle};
+use indicatif::MultiProgress;
 use main_error::MainError;
-use serde::Serialize;
-use std::{fmt::Write, fs::{self, File}, io::BufWriter, path::PathBuf, str::FromStr, time::Duration};
-use tf_demo_parser::demo::{
-    header::Header,
-    parser::{gamestateanalyser::{GameState, GameStateAnalyser}, DemoHandler, RawPacketStream},
+use rayon::prelude::*;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    ffi::OsStr,
+    fs,
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc,
+    },
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
-use tf_demo_parser::Demo;
+use demo_packet_dumper::{
+    diff::{changed_paths, delta, Delta},
+    fields::FieldSelection,
+    filter::{read_steam_ids, PacketFilter, PlayerFilter},
+    check_protocol, parse_demo_with, read_header, schema, stand_in_header,
+    stats::PacketCounts,
+    streams::{StreamKind, StreamOptions, StreamRecord},
+    Error, ParseEvent, ParseOptions, TickOrder,
+};
+use tf_demo_parser::{demo::header::Header, ParseError};
 use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
     fmt::writer::MakeWriterExt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer,
 };
-use rmp_serde::Serializer;
+
+mod follow;
+mod index;
+mod input;
+mod manifest;
+mod output;
+mod progress;
+mod sample;
+mod status;
+mod table;
+mod timing;
+mod verify;
+mod writer;
+
+use follow::follow_demo;
+use index::OffsetIndex;
+use input::{read_demo, read_head, ArchivedDemo};
+use manifest::{is_completed, Manifest, Throughput};
+use output::{
+    guard_outputs, open_output, set_io_retries, write_json, CborSink, ChunkPaths, Compression,
+    CsvSink, FormatHeader, FrameWriter, JsonArraySink, JsonlSink, MsgpackSink, OutputFormat,
+    OutputOptions, OutputStream, RecordSink, Rotation, RotatingWriter, StreamSink,
+    DEFAULT_BUFFER_SIZE, DEFAULT_IO_RETRIES,
+};
+use progress::{BarStyle, Progress, ProgressMode};
+use sample::{IntervalSampler, Sampler};
+use status::{Status, StatusFile};
+use table::player_rows;
+use timing::{header_tickrate, server_tickrate, Tickrate, TimingRecorder};
+use verify::verify_frames;
+use writer::{Frame, WriterThread};
+
+/// Fields that change on every tick without anything meaningful happening, ignored by
+/// `--only-changed`.
+const VOLATILE_FIELDS: &[&str] = &["tick", "simtime"];
+
+/// How many ticks apart `--delta-encode` writes full frames by default, a minute at 66 ticks per
+/// second.
+const DEFAULT_KEYFRAME_INTERVAL: u32 = 3960;
 
 #[allow(clippy::struct_excessive_bools)]
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    #[arg(short, long)]
-    infile: String,
+    /// Demos to parse. Pass `-` to read a single demo from stdin. Demos in a zip archive are
+    /// given as `match.zip:round1.dem`, or `match.zip` if it holds only one.
+    #[arg(short, long, num_args = 1.., required_unless_present_any = ["indir", "emit_schema"])]
+    infile: Vec<String>,
+    /// Parse every `.dem` file in this directory.
+    #[arg(long)]
+    indir: Option<PathBuf>,
+    /// Only parse the demos in --indir recorded on a matching map, e.g. `cp_process` or
+    /// `koth_*`. A pattern with `*` or `?` has to match the whole map name, any other is matched
+    /// as a substring. Either way case doesn't matter.
+    #[arg(long, requires = "indir")]
+    map_filter: Option<String>,
+    /// Map the demos into memory instead of reading them, which keeps memory use down for big
+    /// ones. The demos must not be modified or truncated while they're being parsed.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    mmap: bool,
+    /// Keep parsing a demo that's still being recorded as it grows, like `tail -f`, writing the
+    /// output as the demo comes in. The demo is finished once it stops growing for
+    /// --follow-timeout. Every time it grows the parse carries on from the last complete packet.
+    #[arg(
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        conflicts_with_all = ["mmap", "resume", "validate", "list_streams", "estimate"]
+    )]
+    follow: bool,
+    /// How many seconds a followed demo has to stop growing for to be taken as finished.
+    #[arg(long, default_value_t = 30, requires = "follow")]
+    follow_timeout: u64,
+    /// How many demos to parse at once, 0 for one per CPU.
+    #[arg(long, default_value_t = 1)]
+    threads: usize,
+    /// Name used for the output files instead of the demo's file name.
+    #[arg(long)]
+    name: Option<String>,
+    /// How the output files are named, relative to the output directory. `{stem}` is the
+    /// demo's name, `{map}` and `{ticks}` come from its header and `{kind}` is the output, e.g.
+    /// `gsd` or `chat`. Directories in the template are created as needed.
+    #[arg(long, default_value = "{stem}-{kind}")]
+    name_template: String,
+    /// Output directory. Pass `-` to stream the GameState deltas to stdout.
     #[arg(short, long, default_value = ".")]
     outpath: String,
+    /// Create the output directory if it doesn't exist yet.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    mkdir: bool,
+    /// Parse the demos without writing any output, failing if any packet errors occurred.
+    #[arg(
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        conflicts_with_all = ["resume", "stdout"]
+    )]
+    validate: bool,
+    /// Parse the start of each demo and print which side streams would have records, to pick
+    /// the --dump flags before a full parse. Writes no output.
+    #[arg(
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        conflicts_with_all = ["resume", "stdout", "validate"]
+    )]
+    list_streams: bool,
+    /// Parse the start of each demo with the chosen outputs and print how large each would be
+    /// for the whole demo, before compression. Writes no output.
+    #[arg(
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        conflicts_with_all = ["resume", "stdout", "validate", "list_streams"]
+    )]
+    estimate: bool,
+    /// Skip demos whose GameState (and raw, with --parse-raw) output already exists and isn't empty.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false, conflicts_with = "stdout")]
+    resume: bool,
+    /// Parse every demo even if --resume finds its outputs, and replace existing outputs without a
+    /// warning.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    force: bool,
+    /// Stream the GameState deltas to stdout instead of a file.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false, conflicts_with = "parse_raw")]
+    stdout: bool,
     #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
     parse_raw: bool,
+    /// Only write the raw packets of these types, or that carry a message or user message of
+    /// these types. Comma-separated, e.g. `GameEvent,SayText2`.
+    #[arg(long, value_delimiter = ',', requires = "parse_raw")]
+    raw_filter: Vec<String>,
     #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
     dont_parse_gamestate: bool,
+    /// Don't write the GameState output, for when only the side streams are wanted. The state is
+    /// still tracked for them.
+    #[arg(
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        conflicts_with_all = [
+            "stdout",
+            "only_changed",
+            "fields",
+            "rotate_bytes",
+            "rotate_frames",
+            "sample_frames",
+        ]
+    )]
+    no_gamestate_file: bool,
+    /// Format used for both the GameState delta and raw packet output.
+    #[arg(long, value_enum, default_value_t = OutputFormat::Msgpack)]
+    format: OutputFormat,
+    /// Compression applied to both the GameState delta and raw packet output.
+    #[arg(long, value_enum, default_value_t = Compression::None)]
+    compress: Compression,
+    /// Compression level, 0-9 for gzip and 1-22 for zstd.
+    #[arg(long)]
+    compress_level: Option<i32>,
+    /// Add to the end of existing output files instead of replacing them, e.g. to continue a
+    /// capture with --start-tick. The one-off JSON outputs are still replaced.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false, conflicts_with = "resume")]
+    append: bool,
+    /// Split the GameState output into numbered chunks of at most this many bytes before
+    /// compression, e.g. `{demo_name}-gsd.0.msgpack`. Takes K, M and G suffixes.
+    #[arg(long, value_parser = parse_size, conflicts_with_all = ["stdout", "append"])]
+    rotate_bytes: Option<u64>,
+    /// Split the GameState output into numbered chunks of at most this many frames.
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with_all = ["stdout", "append"]
+    )]
+    rotate_frames: Option<u64>,
+    /// Buffer this many bytes of each output before writing them out. Takes K, M and G
+    /// suffixes.
+    #[arg(long, value_parser = parse_size, default_value_t = DEFAULT_BUFFER_SIZE)]
+    buffer_size: u64,
+    /// Sync every output file to disk before exiting, e.g. when writing to network storage.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    fsync: bool,
+    /// How many times to retry opening an output file that couldn't be, with a growing delay
+    /// between attempts, e.g. when a network mount briefly drops out.
+    #[arg(long, default_value_t = DEFAULT_IO_RETRIES)]
+    io_retries: u32,
+    /// Give up on a demo once this many packets in a row failed to parse.
+    #[arg(long, default_value_t = 100, value_parser = clap::value_parser!(u32).range(1..))]
+    max_consecutive_errors: u32,
+    /// Give up on a demo once this many packets in total failed to parse.
+    #[arg(long)]
+    max_errors: Option<u32>,
+    /// Fail if the parsed frame or tick count differs from what the header says by more than
+    /// 1%, to catch regressions in the parser.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    strict: bool,
+    /// Write the log here instead of a new file in the temp directory.
+    #[arg(long, env = "DEMO_VIS_LOG_FILE")]
+    log_file: Option<PathBuf>,
+    /// Write the log file as JSON lines, with the fields and spans of each event, for ingesting.
+    /// Stderr stays human readable.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    log_json: bool,
+    /// Log every recovered packet error as a warning as it happens, not just the final summary.
+    /// They're logged at debug level otherwise.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    verbose_errors: bool,
+    /// Only print errors to stderr, and no progress bar. An explicit `RUST_LOG` still applies.
+    #[arg(
+        short,
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        conflicts_with = "verbose"
+    )]
+    quiet: bool,
+    /// Print debug logs to stderr, or trace logs when given twice. An explicit `RUST_LOG` still
+    /// applies.
+    #[arg(short, long, action=ArgAction::Count)]
+    verbose: u8,
+    /// Never draw the progress bar. It's also hidden when stderr isn't a terminal.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    no_progress: bool,
+    /// Keep a JSON file here up to date with the parse progress, for other processes to poll.
+    /// With --threads it's of whichever demo was updated last, as named in it.
+    #[arg(long)]
+    status_file: Option<PathBuf>,
+    /// Update the status file every this many ticks.
+    #[arg(long, default_value_t = 1000, requires = "status_file")]
+    status_interval: u64,
+    /// Whether the progress bar follows the ticks or the bytes of the demo parsed.
+    #[arg(long, value_enum, default_value_t = ProgressMode::Ticks)]
+    progress_mode: ProgressMode,
+    /// How the progress bar is drawn. Defaults to fancy on a terminal with a UTF-8 locale, and
+    /// ascii on others. Plain is also written when stderr isn't a terminal.
+    #[arg(long, value_enum)]
+    progress_style: Option<BarStyle>,
+    /// Stop writing a side stream after this many records, as comma-separated `STREAM=N`, e.g.
+    /// `chat=50`. When only capped side streams are written, parsing stops once all are full.
+    #[arg(long, value_parser = parse_record_cap, value_delimiter = ',')]
+    max_records: Vec<(StreamKind, u64)>,
+    /// Only include these players in the GameState output. Comma-separated SteamID64s or in-game names.
+    #[arg(long, value_delimiter = ',')]
+    players: Vec<String>,
+    /// Also include the players listed in this file, one SteamID64 per line, e.g. a watchlist.
+    /// Blank lines and `#` comments are skipped. Merged with any --players.
+    #[arg(long)]
+    players_from_file: Option<PathBuf>,
+    /// The SteamID3s read from --players-from-file, loaded once for every demo.
+    #[arg(skip)]
+    listed_players: HashSet<String>,
+    /// Only write GameState frames that differ from the previously written one, along with the
+    /// paths of the fields that changed.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    only_changed: bool,
+    /// Write the whole GameState only in periodic keyframes, and in between just what changed
+    /// since the previous frame. Keyframes are `{tick, keyframe}` with the whole frame, its structs
+    /// written as maps of their fields even in msgpack. Deltas are `{tick, set, removed}`, where
+    /// `set` holds `[path, value]` pairs to assign and `removed` the paths of object keys to
+    /// delete, applied to the previous frame. Paths are dot separated, e.g. `players.3.health`,
+    /// and index arrays and objects alike. An array that changed length is set whole.
+    #[arg(
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        conflicts_with_all = ["only_changed", "sample_frames", "verify_output"]
+    )]
+    delta_encode: bool,
+    /// Ticks between the keyframes of --delta-encode, the output can be read from any keyframe on.
+    #[arg(
+        long,
+        default_value_t = DEFAULT_KEYFRAME_INTERVAL,
+        value_parser = clap::value_parser!(u32).range(1..),
+        requires = "delta_encode"
+    )]
+    keyframe_interval: u32,
+    /// Read the GameState output back once it's written, checking every frame deserialises and
+    /// that none are missing.
+    #[arg(
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        conflicts_with_all = ["stdout", "append", "only_changed", "fields", "no_gamestate_file"]
+    )]
+    verify_output: bool,
+    /// Count how many of each packet, message and user message type the demo has, logging them
+    /// at the end and adding them to the manifest.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    packet_stats: bool,
+    /// Parse demos recorded with a protocol version the parser doesn't know anyway, instead of
+    /// failing before the first packet. Expect packet errors or a wrong GameState.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    protocol_compat: bool,
+    /// Only write about this many GameState frames, picked at random from across the whole demo.
+    /// The chosen ticks are written to `{demo_name}-sample.json`.
+    #[arg(long, conflicts_with = "only_changed")]
+    sample_frames: Option<usize>,
+    /// Only write a GameState frame about every this many milliseconds of game time, worked out
+    /// from the tick rate, instead of one every tick.
+    #[arg(
+        long,
+        value_parser = clap::value_parser!(u64).range(1..),
+        conflicts_with = "sample_frames"
+    )]
+    dump_interval_ms: Option<u64>,
+    /// Pick the sampled frames evenly spaced over the demo's ticks instead of at random.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false, requires = "sample_frames")]
+    deterministic: bool,
+    /// Seed for the random frame sampling, to pick the same frames again.
+    #[arg(long, requires = "sample_frames", conflicts_with = "deterministic")]
+    sample_seed: Option<u64>,
+    /// Only write these GameState fields, as comma-separated paths like `players.health`.
+    /// The tick is always kept.
+    #[arg(long, value_delimiter = ',')]
+    fields: Vec<String>,
+    /// Write chat and server text messages to `{demo_name}-chat.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_chat: bool,
+    /// Write every kill to `{demo_name}-deaths.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_deaths: bool,
+    /// Write sampled player positions to `{demo_name}-positions.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_positions: bool,
+    /// Sample positions every this many ticks.
+    #[arg(long, default_value_t = 1, requires = "dump_positions")]
+    position_stride: u32,
+    /// Fill in the positions of players who weren't updated for a few ticks by interpolating
+    /// between the updates either side, marked as `interpolated`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false, requires = "dump_positions")]
+    interpolate_positions: bool,
+    /// Write a JSON Schema for every output's records to `schema.json` in the output directory,
+    /// or stdout, and exit without parsing anything. The one-shot JSON documents are
+    /// `{header, records}`, the schemas describe each of the records.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false, conflicts_with = "validate")]
+    emit_schema: bool,
+    /// Write the demo header to `{demo_name}-header.json` as soon as it's read.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_header: bool,
+    /// Record the SHA-256 of each demo in its manifest, to spot duplicate demos and tell which
+    /// demo a dump came from.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    sha256: bool,
+    /// Write the start, end and result of each round to `{demo_name}-rounds.json`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_rounds: bool,
+    /// Write each player's active weapon and carried weapons to `{demo_name}-loadouts.jsonl`
+    /// whenever they change.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_loadouts: bool,
+    /// Write when each player starts and stops talking to `{demo_name}-voice-activity.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_voice_activity: bool,
+    /// Write every change to the team scores, captures and round wins to
+    /// `{demo_name}-scoreboard.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_scoreboard: bool,
+    /// Write every bit of damage and healing between players to `{demo_name}-combat-log.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_combat_log: bool,
+    /// Write each player's total damage and healing to `{demo_name}-combat-summary.json`. The
+    /// totals always cover the whole demo, --tick-range and --start-tick only narrow the
+    /// combat log.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    combat_summary: bool,
+    /// Write each projectile's owner, type, spawn position and lifetime to
+    /// `{demo_name}-projectiles.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_projectiles: bool,
+    /// Write every class switch to `{demo_name}-class-changes.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_class_changes: bool,
+    /// Also write a class change when a player respawns as the class they already were.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false, requires = "dump_class_changes")]
+    class_change_respawns: bool,
+    /// Write each medic's ubercharge level and when it's deployed to
+    /// `{demo_name}-ubercharge.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_ubercharge: bool,
+    /// Debugging aid: write every entity create, update and delete to
+    /// `{demo_name}-entity-events.jsonl`. Very verbose, and not a stable output.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_entity_events: bool,
+    /// Write every change to a player's scoreboard points to `{demo_name}-player-scores.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_player_scores: bool,
+    /// Write the placement, construction, upgrades, sappers, health and destruction of every
+    /// sentry, dispenser and teleporter to `{demo_name}-buildings.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_buildings: bool,
+    /// Write each player's final score, kills, deaths, assists, time on each class and connect
+    /// and disconnect ticks to `{demo_name}-players.json`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    player_summary: bool,
+    /// For POV demos, write the recording player's own view angles every tick to
+    /// `{demo_name}-recorder.jsonl`, tagged `is_recorder` to join with their GameState frames.
+    /// SourceTV demos have no recorder, so the file stays empty.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_recorder: bool,
+    /// Write every player joining a team, spectating or disconnecting, starting from the team
+    /// they're first seen on, to `{demo_name}-team-changes.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_team_changes: bool,
+    /// Write the pauses and round restarts, e.g. from `mp_tournament_restart`, to
+    /// `{demo_name}-events.json`. With `--dump-timing` the paused frames are marked either way.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_match_events: bool,
+    /// Write every player leaving the server, with the reason, to `{demo_name}-disconnects.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_disconnects: bool,
+    /// Write the item definitions of the weapons and cosmetics each player had equipped to
+    /// `{demo_name}-items.json`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_items: bool,
+    /// Write where each living player was looking, whenever it changes, to
+    /// `{demo_name}-angles.jsonl`. A POV demo has the recorder's own angles and the server's for
+    /// the players it was sent, a SourceTV demo the server's for everyone.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_angles: bool,
+    /// Write the string tables as of the end of the demo, e.g. `userinfo` and
+    /// `instancebaseline`, to `{demo_name}-stringtables.json`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_stringtables: bool,
+    /// Write how long each player spent dead and then in spawn, for every life, to
+    /// `{demo_name}-respawn-timing.jsonl`. Leaving spawn is judged by how far the player got
+    /// from where they spawned.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_respawn_timing: bool,
+    /// Write CTF flag and A/D intelligence pickups, captures, drops, defends and returns to
+    /// `{demo_name}-flag-events.jsonl`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_flag_events: bool,
+    /// Write the server's name and settings from connecting, along with its replicated convars
+    /// such as `mp_tournament` or `sv_cheats` and any later changes to them, to
+    /// `{demo_name}-serverinfo.json`.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_serverinfo: bool,
+    /// Write the time in seconds of every GameState frame to `{demo_name}-timing.json`, for
+    /// syncing the output to a recording of the match.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    dump_timing: bool,
+    /// Write where each tick's packets start in the demo to `{demo_name}-index.json`, with the
+    /// nearest full entity update before each, for seeking in a viewer.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false)]
+    emit_offset_index: bool,
+    /// Start the timing at the first round start rather than the first tick of the demo.
+    #[arg(long, action=ArgAction::SetTrue, default_value_t = false, requires = "dump_timing")]
+    align_to_round_start: bool,
+    /// Ticks per second to convert ticks to seconds with, for servers that record at a
+    /// non-standard rate. Otherwise it's worked out from the server info or the header, and a
+    /// mismatch between the two is warned about.
+    #[arg(long, value_parser = parse_tickrate)]
+    tickrate: Option<f64>,
+    /// Parse packet data without a demo header, e.g. a fragment cut out of a demo, from the
+    /// start of the file. It has to start at a packet, and needs --tickrate since nothing else
+    /// gives it.
+    #[arg(
+        long,
+        action=ArgAction::SetTrue,
+        default_value_t = false,
+        requires = "tickrate",
+        conflicts_with_all = ["validate", "strict"]
+    )]
+    no_header: bool,
+    /// The map to record for a --no-header fragment.
+    #[arg(long, requires = "no_header")]
+    map: Option<String>,
+    /// Only write output for ticks within this inclusive `START:END` window.
+    #[arg(long, value_parser = parse_tick_range)]
+    tick_range: Option<(u32, u32)>,
+    /// Only write output from this tick on. The ticks before it are still parsed, so the state
+    /// is correct once output starts.
+    #[arg(long, conflicts_with = "tick_range")]
+    start_tick: Option<u32>,
+    /// Stop parsing once the server tick passes this.
+    #[arg(long)]
+    max_ticks: Option<u32>,
 }
 
+/// Parses an inclusive `START:END` tick window.
+fn parse_tick_range(range: &str) -> Result<(u32, u32), String> {
+    let (start, end) = range
+        .split_once(':')
+        .ok_or_else(|| format!("'{range}' is not a tick range, expected START:END"))?;
+    let start: u32 = start
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid tick range start '{start}': {e}"))?;
+    let end: u32 = end
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid tick range end '{end}': {e}"))?;
+    if start > end {
+        return Err(format!("tick range start {start} is after its end {end}"));
+    }
+    Ok((start, end))
+}
+
+/// Parses a `STREAM=N` record cap, with the stream named as in its output file.
+fn parse_record_cap(cap: &str) -> Result<(StreamKind, u64), String> {
+    let (name, count) = cap
+        .split_once('=')
+        .ok_or_else(|| format!("expected STREAM=N, found '{cap}'"))?;
+    let kind = StreamKind::ALL
+        .iter()
+        .copied()
+        .find(|kind| kind.name() == name.trim())
+        .ok_or_else(|| format!("unknown stream '{name}'"))?;
+    let count = count
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid record count '{count}': {e}"))?;
+    Ok((kind, count))
+}
+
+/// Parses a byte count, optionally with a binary `K`, `M` or `G` suffix.
+fn parse_size(size: &str) -> Result<u64, String> {
+    let size = size.trim();
+    let (digits, unit) = match size.char_indices().find(|(_, c)| !c.is_ascii_digit()) {
+        Some((split, _)) => size.split_at(split),
+        None => (size, ""),
+    };
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        _ => return Err(format!("unknown size unit '{unit}', expected K, M or G")),
+    };
+    let amount: u64 = digits
+        .parse()
+        .map_err(|e| format!("invalid size '{size}': {e}"))?;
+    match amount.checked_mul(multiplier) {
+        Some(0) => Err("size has to be more than 0".to_string()),
+        Some(bytes) => Ok(bytes),
+        None => Err(format!("size '{size}' is too large")),
+    }
+}
+
+/// Parses a tick rate, which every tick to seconds conversion divides by.
+fn parse_tickrate(tickrate: &str) -> Result<f64, String> {
+    let tickrate: f64 = tickrate
+        .trim()
+        .parse()
+        .map_err(|e| format!("invalid tick rate '{tickrate}': {e}"))?;
+    if !tickrate.is_finite() || tickrate <= 0.0 {
+        return Err(format!("tick rate {tickrate} has to be a positive number"));
+    }
+    Ok(tickrate)
+}
+
+/// Exit code used when the run was stopped with Ctrl-C, as a shell would report SIGINT.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
 fn main() -> Result<(), MainError> {
-    let _guard = init_tracing();
-    let args = Args::parse();
-    if let Err(e) = fs::read_dir(&args.outpath) {
-        panic!(
-            "Error: 'outpath' argument was invalid. Make sure it exists, and is a directory. {e}"
+    let mut args = Args::parse();
+    let log_file = args.log_file.take().unwrap_or_else(default_log_file);
+    let guard = init_tracing(&log_file, log_filter(&args), args.log_json);
+    // Only kept when the file could actually be created, so the manifest doesn't point nowhere
+    args.log_file = guard.is_some().then_some(log_file);
+    set_io_retries(args.io_retries);
+    let to_stdout = args.stdout || args.outpath == "-";
+    if to_stdout && args.parse_raw {
+        return Err("Raw packet output can't be combined with stdout output.".into());
+    }
+    if args.format == OutputFormat::Csv {
+        // The rows are a fixed projection of whole frames, raw packets don't flatten at all
+        if args.parse_raw || args.only_changed || !args.fields.is_empty() {
+            return Err(
+                "--format csv can't be combined with --parse-raw, --only-changed or --fields."
+                    .into(),
+            );
+        }
+        if args.append {
+            return Err("--format csv can't be combined with --append.".into());
+        }
+        if args.delta_encode {
+            return Err("--format csv can't be combined with --delta-encode.".into());
+        }
+        if args.verify_output {
+            return Err("--format csv can't be verified, the rows don't read back as frames.".into());
+        }
+        if args.estimate {
+            return Err("--format csv can't be estimated, only whole frames are sampled.".into());
+        }
+    }
+    if to_stdout && args.resume {
+        return Err("--resume can't be combined with stdout output.".into());
+    }
+    if !to_stdout && !args.validate && !args.list_streams && !args.estimate {
+        // A file in the way gets the error below rather than a confusing one from creating it
+        if args.mkdir && !Path::new(&args.outpath).exists() {
+            fs::create_dir_all(&args.outpath).map_err(|e| {
+                format!("Couldn't create 'outpath' directory '{}': {e}", args.outpath)
+            })?;
+        }
+        check_outpath(&args.outpath)?;
+    }
+
+    if args.emit_schema {
+        return emit_schema(&args, to_stdout);
+    }
+
+    if !args.name_template.contains("{kind}") {
+        return Err(
+            "--name-template has to contain {kind}, or every output would share a file.".into(),
+        );
+    }
+
+    // A cap on a stream that isn't written would never fill, and keep the parse from stopping
+    let streams = requested_streams(&args);
+    if let Some(&(kind, _)) = args.max_records.iter().find(|(kind, _)| !streams.contains(kind)) {
+        return Err(format!(
+            "--max-records caps {}, which isn't being written. It needs {}.",
+            kind.name(),
+            stream_flag(kind)
+        )
+        .into());
+    }
+
+    if let Some(path) = &args.players_from_file {
+        let file = fs::File::open(path).map_err(|e| {
+            format!("Couldn't open --players-from-file '{}': {e}", path.display())
+        })?;
+        args.listed_players = read_steam_ids(io::BufReader::new(file))
+            .map_err(|e| format!("Couldn't read --players-from-file '{}': {e}", path.display()))?;
+        if args.listed_players.is_empty() {
+            return Err(
+                format!("--players-from-file '{}' lists no players.", path.display()).into(),
+            );
+        }
+        tracing::info!("Loaded {} players from {}.", args.listed_players.len(), path.display());
+    }
+
+    let infiles = collect_inputs(&args)?;
+    if infiles.len() > 1 && args.name.is_some() {
+        return Err("--name can only be used when parsing a single demo.".into());
+    }
+    if infiles.len() > 1 && (to_stdout || infiles.iter().any(|infile| infile == "-")) {
+        return Err("stdin and stdout can only be used when parsing a single demo.".into());
+    }
+    if args.follow {
+        if infiles.len() > 1 {
+            return Err("--follow can only be used when parsing a single demo.".into());
+        }
+        if infiles
+            .iter()
+            .any(|infile| infile == "-" || ArchivedDemo::parse(infile).is_some())
+        {
+            return Err("--follow needs a demo file, it can't follow stdin or an archive.".into());
+        }
+    }
+    // A demo in an archive is protected by protecting the archive
+    guard_outputs(
+        infiles.iter().map(|infile| {
+            Path::new(ArchivedDemo::parse(infile).map_or(infile.as_str(), |demo| demo.archive))
+        }),
+        !args.force,
+    );
+
+    // The handler only flips the flag, the parse loop notices it and winds down normally
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let handler_flag = Arc::clone(&interrupted);
+    if let Err(e) = ctrlc::set_handler(move || handler_flag.store(true, Ordering::Relaxed)) {
+        tracing::warn!("Couldn't install the Ctrl-C handler, interrupting will lose output: {e}");
+    }
+
+    let multi = MultiProgress::new();
+    let result = if let [infile] = infiles.as_slice() {
+        dump_demo(&args, infile, &multi, &interrupted)
+    } else {
+        dump_all(&args, &infiles, &multi, &interrupted)
+    };
+    if interrupted.load(Ordering::Relaxed) {
+        tracing::warn!("Interrupted, the output written so far has been kept.");
+        // Exiting skips destructors, so the log has to be flushed first
+        drop(guard);
+        process::exit(INTERRUPTED_EXIT_CODE);
+    }
+    result
+}
+
+/// Writes the schemas of all outputs as a single JSON object keyed by output kind.
+fn emit_schema(args: &Args, to_stdout: bool) -> Result<(), MainError> {
+    let schemas = schema::schemas();
+    if to_stdout {
+        let mut stdout = io::stdout().lock();
+        serde_json::to_writer_pretty(&mut stdout, &schemas)?;
+        writeln!(stdout)?;
+    } else {
+        let path = Path::new(&args.outpath).join("schema.json");
+        write_json(&path, &schemas, args.fsync)
+            .map_err(|e| format!("Couldn't write schema to {}: {e}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Parses each demo in turn, carrying on past the ones that fail.
+fn dump_all(
+    args: &Args,
+    infiles: &[String],
+    multi: &MultiProgress,
+    interrupted: &Arc<AtomicBool>,
+) -> Result<(), MainError> {
+    let failures = AtomicUsize::new(0);
+    let dump = |infile: &String| {
+        if interrupted.load(Ordering::Relaxed) {
+            return;
+        }
+        if let Err(e) = dump_demo(args, infile, multi, interrupted) {
+            tracing::error!("Failed to parse {infile}, continuing with the next demo: {e:?}");
+            failures.fetch_add(1, Ordering::Relaxed);
+        }
+    };
+    if args.threads == 1 {
+        infiles.iter().for_each(dump);
+    } else {
+        // Each demo is still parsed on a single thread, only separate demos run side by side
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(args.threads)
+            .build()
+            .map_err(|e| format!("Couldn't start {} worker threads: {e}", args.threads))?;
+        pool.install(|| infiles.par_iter().for_each(dump));
+    }
+    let failed = failures.into_inner();
+    tracing::info!(
+        "Parsed {} of {} demos, {failed} failed.",
+        infiles.len() - failed,
+        infiles.len()
+    );
+    if failed > 0 {
+        return Err(format!("{failed} of {} demos failed to parse.", infiles.len()).into());
+    }
+    Ok(())
+}
+
+/// Checks that the outputs can go in `outpath`, telling apart the usual ways it can't.
+fn check_outpath(outpath: &str) -> Result<(), String> {
+    let describe = |e: io::Error| match e.kind() {
+        io::ErrorKind::NotFound => format!(
+            "'outpath' directory '{outpath}' doesn't exist. Create it, or pass --mkdir to have it \
+             created."
+        ),
+        io::ErrorKind::PermissionDenied => {
+            format!("'outpath' directory '{outpath}' isn't accessible, permission denied: {e}")
+        }
+        _ => format!("'outpath' directory '{outpath}' can't be used: {e}"),
+    };
+    let metadata = fs::metadata(outpath).map_err(describe)?;
+    if !metadata.is_dir() {
+        return Err(format!(
+            "'outpath' '{outpath}' is a file, not a directory. Pass the directory to write to, the \
+             outputs are named after the demo, or use --name to pick their name."
+        ));
+    }
+    // Existing doesn't mean it can be read from
+    fs::read_dir(outpath).map(drop).map_err(describe)
+}
+
+/// The demos given by `--infile` and `--indir`, in that order.
+fn collect_inputs(args: &Args) -> Result<Vec<String>, MainError> {
+    let mut infiles = args.infile.clone();
+    if let Some(indir) = &args.indir {
+        let mut found = Vec::new();
+        for entry in fs::read_dir(indir)? {
+            let path = entry?.path();
+            if path.is_file() && has_demo_extension(&path) {
+                found.push(path.to_string_lossy().into_owned());
+            }
+        }
+        found.sort();
+        if found.is_empty() {
+            tracing::warn!("No demos found in {}.", indir.display());
+        }
+        if let Some(pattern) = &args.map_filter {
+            let total = found.len();
+            found.retain(|infile| on_matching_map(infile, pattern));
+            if found.is_empty() && total > 0 {
+                tracing::warn!(
+                    "None of the {total} demos in {} are on a map matching '{pattern}'.",
+                    indir.display()
+                );
+            }
+        }
+        infiles.extend(found);
+    }
+    Ok(infiles)
+}
+
+/// The side streams the --dump flags ask for.
+fn requested_streams(args: &Args) -> Vec<StreamKind> {
+    let mut streams = Vec::new();
+    if args.dump_chat {
+        streams.push(StreamKind::Chat);
+    }
+    if args.dump_deaths {
+        streams.push(StreamKind::Deaths);
+    }
+    if args.dump_positions {
+        streams.push(StreamKind::Positions);
+    }
+    if args.dump_rounds {
+        streams.push(StreamKind::Rounds);
+    }
+    if args.dump_loadouts {
+        streams.push(StreamKind::Loadouts);
+    }
+    if args.dump_voice_activity {
+        streams.push(StreamKind::VoiceActivity);
+    }
+    if args.dump_scoreboard {
+        streams.push(StreamKind::Scoreboard);
+    }
+    if args.dump_combat_log {
+        streams.push(StreamKind::CombatLog);
+    }
+    if args.combat_summary {
+        streams.push(StreamKind::CombatSummary);
+    }
+    if args.dump_projectiles {
+        streams.push(StreamKind::Projectiles);
+    }
+    if args.dump_class_changes {
+        streams.push(StreamKind::ClassChanges);
+    }
+    if args.dump_ubercharge {
+        streams.push(StreamKind::Ubercharge);
+    }
+    if args.dump_entity_events {
+        streams.push(StreamKind::EntityEvents);
+    }
+    if args.dump_player_scores {
+        streams.push(StreamKind::PlayerScores);
+    }
+    if args.dump_buildings {
+        streams.push(StreamKind::Buildings);
+    }
+    if args.player_summary {
+        streams.push(StreamKind::Players);
+    }
+    if args.dump_recorder {
+        streams.push(StreamKind::Recorder);
+    }
+    if args.dump_team_changes {
+        streams.push(StreamKind::TeamChanges);
+    }
+    if args.dump_match_events {
+        streams.push(StreamKind::MatchEvents);
+    }
+    if args.dump_disconnects {
+        streams.push(StreamKind::Disconnects);
+    }
+    if args.dump_items {
+        streams.push(StreamKind::Items);
+    }
+    if args.dump_angles {
+        streams.push(StreamKind::Angles);
+    }
+    if args.dump_stringtables {
+        streams.push(StreamKind::StringTables);
+    }
+    if args.dump_respawn_timing {
+        streams.push(StreamKind::RespawnTiming);
+    }
+    if args.dump_flag_events {
+        streams.push(StreamKind::FlagEvents);
+    }
+    if args.dump_serverinfo {
+        streams.push(StreamKind::ServerInfo);
+    }
+    streams
+}
+
+fn stream_options(args: &Args) -> StreamOptions {
+    StreamOptions {
+        position_stride: args.position_stride,
+        interpolate_positions: args.interpolate_positions,
+        class_change_respawns: args.class_change_respawns,
+    }
+}
+
+/// Parses one demo and writes all of its outputs.
+fn dump_demo(
+    args: &Args,
+    infile: &str,
+    multi: &MultiProgress,
+    interrupted: &Arc<AtomicBool>,
+) -> Result<(), MainError> {
+    let to_stdout = args.stdout || args.outpath == "-";
+    let from_stdin = infile == "-";
+    let demo_name = match &args.name {
+        Some(name) => name.as_str(),
+        None if from_stdin => {
+            tracing::warn!("Reading from stdin without --name, output files will be named 'stdin'.");
+            "stdin"
+        }
+        None => demo_stem(infile)?,
+    };
+    let outpath = if to_stdout { "." } else { args.outpath.as_str() };
+    let path = PathBuf::from(outpath);
+    // Reading a file's header is cheap, so --resume still doesn't have to load the whole demo
+    let mut file = None;
+    let stand_in = args
+        .no_header
+        .then(|| stand_in_header(args.map.as_deref().unwrap_or_default()));
+    let header = if let Some(stand_in) = &stand_in {
+        Some(stand_in.clone())
+    } else if OutputNames::needs_header(&args.name_template) {
+        if from_stdin {
+            file = Some(read_demo(infile, args.mmap)?);
+        }
+        let header = match &file {
+            Some(file) => read_header(file),
+            None => read_header(&read_head(infile)?),
+        };
+        Some(header?)
+    } else {
+        None
+    };
+    let names = OutputNames::new(&path, &args.name_template, demo_name, header.as_ref());
+    let extension = format!("{}{}", args.format.extension(), args.compress.suffix());
+    let gs_path = names.path("gsd", &extension);
+    let raw_path = names.path("raw", &extension);
+    let rotation = Rotation {
+        max_bytes: args.rotate_bytes,
+        max_records: args.rotate_frames,
+    };
+    let rotated = rotation.max_bytes.is_some() || rotation.max_records.is_some();
+    // Given an empty extension the path ends in the dot the chunk index goes after
+    let chunk_paths = ChunkPaths::new(names.path("gsd", ""), &extension);
+    let first_gs_path = if rotated {
+        chunk_paths.path(0)
+    } else {
+        gs_path.clone()
+    };
+
+    let manifest_path = names.path("manifest", "json");
+    // Without a GameState output, the manifest is what marks a demo done
+    let done = if args.no_gamestate_file {
+        is_completed(&manifest_path)
+    } else {
+        is_done(&first_gs_path)
+    };
+    if args.resume && !args.force && done && (!args.parse_raw || is_done(&raw_path)) {
+        tracing::info!("Skipping {infile}, its output already exists in {}.", path.display());
+        return Ok(());
+    }
+
+    let file = match file {
+        Some(file) => file,
+        None => read_demo(infile, args.mmap)?,
+    };
+
+    if args.validate {
+        return validate_demo(args, infile, &file, demo_name, multi, interrupted);
+    }
+    if args.list_streams {
+        return list_streams(args, &file, demo_name, multi, interrupted);
+    }
+    if args.estimate {
+        return estimate_output(args, &file, demo_name, multi, interrupted);
+    }
+
+    tracing::info!("Generating {:?} serialisers...", args.format);
+
+    let output_options = OutputOptions {
+        compression: args.compress,
+        level: args.compress_level,
+        fsync: args.fsync,
+        append: args.append,
+        buffer_size: args.buffer_size as usize,
+    };
+    // Side streams are written as JSON Lines, or a JSON document for the one-shot ones
+    let streams = requested_streams(args);
+    let mut stream_writers = HashMap::new();
+    for &kind in &streams {
+        let format = if kind.is_document() {
+            OutputFormat::Json
+        } else {
+            OutputFormat::Jsonl
+        };
+        let stream_path = names.path(
+            kind.name(),
+            &format!("{}{}", format.extension(), args.compress.suffix()),
+        );
+        let writer =
+            StreamSink::new(format, open_output(Some(&stream_path), format, &output_options)?);
+        tracing::info!("Generated {} serialiser with file {:?}.", kind.name(), &stream_path);
+        stream_writers.insert(kind, writer);
+    }
+
+    // The bar is only drawn on an interactive terminal, otherwise progress is logged instead
+    let progress = new_progress(args, multi, demo_name);
+
+    // How many more records each capped stream takes
+    let mut remaining_records: HashMap<StreamKind, u64> =
+        args.max_records.iter().copied().collect();
+    // Past the caps there's nothing left to write, unless the GameState or raw output wants the
+    // rest of the demo
+    let stop_when_capped = !args.parse_raw
+        && (args.dont_parse_gamestate || args.no_gamestate_file)
+        && !streams.is_empty()
+        && streams.iter().all(|kind| remaining_records.contains_key(kind));
+    let capped = Arc::new(AtomicBool::new(false));
+
+    // Aligning the timing needs the rounds, even when they aren't written
+    let mut parse_streams = streams.clone();
+    if args.align_to_round_start && !parse_streams.contains(&StreamKind::Rounds) {
+        parse_streams.push(StreamKind::Rounds);
+    }
+    // And marking the paused frames needs the pauses
+    if args.dump_timing && !parse_streams.contains(&StreamKind::MatchEvents) {
+        parse_streams.push(StreamKind::MatchEvents);
+    }
+    // As do the CSV rows for the scores
+    let csv = args.format == OutputFormat::Csv;
+    if csv && !parse_streams.contains(&StreamKind::PlayerScores) {
+        parse_streams.push(StreamKind::PlayerScores);
+    }
+    let opts = ParseOptions {
+        parse_raw: args.parse_raw,
+        parse_gamestate: !args.dont_parse_gamestate && !args.no_gamestate_file,
+        tick_range: args
+            .tick_range
+            .or(args.start_tick.map(|start_tick| (start_tick, u32::MAX))),
+        max_ticks: args.max_ticks,
+        streams: parse_streams,
+        stream_options: stream_options(args),
+        max_consecutive_errors: args.max_consecutive_errors,
+        max_total_errors: args.max_errors,
+        stop: vec![Arc::clone(interrupted), Arc::clone(&capped)],
+        header: stand_in,
+        packet_stats: args.packet_stats,
+        protocol_compat: args.protocol_compat,
+    };
+    if args.no_header {
+        tracing::warn!(
+            "Parsing without a header, the GameState stays incomplete until the fragment's first \
+             full entity update, and the data tables it may be missing can't be recovered."
+        );
+    }
+    let mut player_filter = (!args.players.is_empty() || !args.listed_players.is_empty())
+        .then(|| {
+            let mut filter = PlayerFilter::new(&args.players);
+            filter.extend(&args.listed_players);
+            filter
+        });
+    let mut raw_filter =
+        (!args.raw_filter.is_empty()).then(|| PacketFilter::new(&args.raw_filter));
+    let fields = (!args.fields.is_empty()).then(|| FieldSelection::new(&args.fields));
+    let mut produced = Vec::new();
+    if !args.no_gamestate_file {
+        produced.push("gsd");
+    }
+    if args.parse_raw {
+        produced.push("raw");
+    }
+    if args.dump_header {
+        produced.push("header");
+    }
+    if args.dump_timing {
+        produced.push("timing");
+    }
+    if args.emit_offset_index {
+        produced.push("index");
+    }
+    if args.sample_frames.is_some() {
+        produced.push("sample");
+    }
+    produced.extend(streams.iter().map(|kind| kind.name()));
+    let mut manifest = Manifest::new(infile, produced);
+    manifest.log_file = args.log_file.clone();
+    let header_path = names.path("header", "json");
+    let timing_path = names.path("timing", "json");
+    let index_path = names.path("index", "json");
+    let sample_path = names.path("sample", "json");
+
+    // The last frame written in --only-changed or --delta-encode mode, to diff the next one
+    // against
+    let mut previous_frame: Option<serde_json::Value> = None;
+    let mut last_keyframe: Option<u32> = None;
+    let gsd_output = if args.no_gamestate_file {
+        GsdOutput::Skipped
+    } else if rotated {
+        GsdOutput::Chunks(rotation, chunk_paths)
+    } else {
+        GsdOutput::Single(if to_stdout { None } else { Some(gs_path.as_path()) })
+    };
+    let raw_target = args.parse_raw.then_some(raw_path.as_path());
+    let writer = match args.format {
+        OutputFormat::Msgpack => spawn_writer(
+            MsgpackSink::new,
+            args.format,
+            &output_options,
+            gsd_output,
+            raw_target,
+            stream_writers,
+        ),
+        OutputFormat::Jsonl => spawn_writer(
+            JsonlSink::new,
+            args.format,
+            &output_options,
+            gsd_output,
+            raw_target,
+            stream_writers,
+        ),
+        OutputFormat::Cbor => spawn_writer(
+            CborSink::new,
+            args.format,
+            &output_options,
+            gsd_output,
+            raw_target,
+            stream_writers,
+        ),
+        OutputFormat::Csv => spawn_writer(
+            CsvSink::new,
+            args.format,
+            &output_options,
+            gsd_output,
+            raw_target,
+            stream_writers,
+        ),
+        OutputFormat::Json => spawn_writer(
+            JsonArraySink::new,
+            args.format,
+            &output_options,
+            gsd_output,
+            raw_target,
+            stream_writers,
+        ),
+    }?;
+    let mut errors = ErrorSummary::default();
+    let mut status_file = args
+        .status_file
+        .as_deref()
+        .map(|path| StatusFile::new(path, args.status_interval, demo_name));
+    let mut total_ticks = 0;
+    let mut last_tick = 0;
+    let mut bytes_read = 0;
+    let mut parsed_frames = 0;
+    let mut timing = None;
+    let mut index = args.emit_offset_index.then(OffsetIndex::default);
+    let mut sampler = None;
+    let mut tick_order = TickOrder::default();
+    let mut written_tick = None;
+    let mut interval = args
+        .dump_interval_ms
+        .map(|interval_ms| IntervalSampler::new(Duration::from_millis(interval_ms)));
+    // Double options, to tell a header without a usable rate apart from not having a header
+    let mut header_rate = None;
+    let mut server_rate = None;
+    // The latest score of each player by SteamID, for the CSV rows
+    let mut scores = HashMap::new();
+    tracing::info!("Parsing demo...");
+    let input_len = file.len() as u64;
+    let mut handle_event = |event: ParseEvent<'_, '_>| -> Result<(), Error> {
+        match event {
+            ParseEvent::Header(header) => {
+                tracing::info!("Success! Preparing to handle packet stream...");
+                if args.protocol_compat {
+                    if let Err(e) = check_protocol(header) {
+                        tracing::warn!("{e}, parsing it anyway for --protocol-compat.");
+                    }
+                }
+                // Written before any packets, so it's there even if the demo turns out broken
+                if args.dump_header {
+                    write_json(&header_path, header, args.fsync).map_err(Error::output)?;
+                    tracing::info!("Wrote header to {:?}.", &header_path);
+                }
+                if args.dump_timing {
+                    timing = Some(TimingRecorder::new(args.align_to_round_start));
+                }
+                header_rate = Some(header_tickrate(header));
+                writer.start(FormatHeader::new(header.protocol))?;
+                manifest.header = Some(header.clone());
+                let total = args
+                    .max_ticks
+                    .map_or(header.ticks, |max_ticks| header.ticks.min(max_ticks));
+                // Only the frames inside the window are offered, so that's what gets divided up
+                let window_ticks = match opts.tick_range {
+                    Some((start, end)) => total.min(end.saturating_add(1)).saturating_sub(start),
+                    None => total,
+                };
+                if let Some(frames) = args.sample_frames {
+                    sampler = Some(if !args.deterministic {
+                        Sampler::reservoir(frames, args.sample_seed)
+                    } else if window_ticks > 0 {
+                        Sampler::strided(frames, window_ticks)
+                    } else {
+                        tracing::warn!(
+                            "The header has no ticks in the window to space the sampled frames \
+                             over, picking them at random instead."
+                        );
+                        Sampler::reservoir(frames, None)
+                    });
+                }
+                progress.start(total as u64, input_len);
+                total_ticks = total;
+            }
+            ParseEvent::Tick {
+                tick,
+                bytes_read: read,
+                bit_position,
+                full_update,
+            } => {
+                progress.tick(read);
+                last_tick = tick;
+                bytes_read = read;
+                if let Some(timing) = timing.as_mut() {
+                    timing.tick(tick);
+                }
+                if let Some(index) = index.as_mut() {
+                    index.tick(tick, bit_position, full_update);
+                }
+                if let Some(status_file) = status_file.as_mut() {
+                    if status_file.due(progress.ticks()) {
+                        let mut status = Status::new(
+                            demo_name,
+                            tick,
+                            total_ticks,
+                            progress.ticks(),
+                            progress.elapsed(),
+                        );
+                        status.errors = errors.total();
+                        status_file.write(&status);
+                    }
+                }
+            }
+            ParseEvent::ServerInfo(info) => {
+                server_rate = server_tickrate(info.interval_per_tick);
+            }
+            ParseEvent::RawPacket(packet) => {
+                if raw_filter.as_mut().is_some_and(|filter| !filter.matches(packet)) {
+                    return Ok(());
+                }
+                writer.write_raw(args.format.encode(packet)?)?;
+                manifest.raw_packets += 1;
+            }
+            ParseEvent::GameState(output) => {
+                let tick: u32 = output.tick.into();
+                // Consumers rely on each frame having a later tick than the one before, which
+                // resyncing after packet errors could break
+                if let Err(previous) = tick_order.advance(tick) {
+                    tracing::warn!(
+                        "Skipped the GameState at tick {tick}, it doesn't come after the previous \
+                         frame at tick {previous}."
+                    );
+                    return Ok(());
+                }
+                if let Some(interval) = interval.as_mut() {
+                    // The server info comes with the signon packets, so it's known by now
+                    let tickrate =
+                        Tickrate::settle(args.tickrate, server_rate, header_rate.flatten());
+                    if !interval.due(tick, tickrate.tickrate) {
+                        return Ok(());
+                    }
+                }
+                let mut output = output.clone();
+                if let Some(filter) = player_filter.as_mut() {
+                    filter.apply(&mut output);
+                }
+                let frames = if csv {
+                    player_rows(&output, &scores).map(Frame::Row).collect()
+                } else {
+                    let state = match &fields {
+                        Some(fields) => {
+                            let value = serde_json::to_value(&output).map_err(Error::output)?;
+                            Frame::Projected(fields.project(&value))
+                        }
+                        None => Frame::Full(output),
+                    };
+                    let frame = if args.only_changed {
+                        let value = serde_json::to_value(&state).map_err(Error::output)?;
+                        let changed = match &previous_frame {
+                            Some(previous) => changed_paths(previous, &value, VOLATILE_FIELDS),
+                            // Nothing to diff the first frame against, the root path marks it all
+                            // changed
+                            None => vec![String::new()],
+                        };
+                        if changed.is_empty() {
+                            return Ok(());
+                        }
+                        previous_frame = Some(value);
+                        Frame::Changed {
+                            tick,
+                            changed,
+                            state: Box::new(state),
+                        }
+                    } else if args.delta_encode {
+                        let value = serde_json::to_value(&state).map_err(Error::output)?;
+                        let keyframe_due = last_keyframe
+                            .is_none_or(|keyframe| tick - keyframe >= args.keyframe_interval);
+                        let frame = match &previous_frame {
+                            Some(previous) if !keyframe_due => {
+                                let Delta { set, removed } = delta(previous, &value);
+                                Frame::Delta { tick, set, removed }
+                            }
+                            _ => {
+                                last_keyframe = Some(tick);
+                                Frame::Keyframe {
+                                    tick,
+                                    keyframe: value.clone(),
+                                }
+                            }
+                        };
+                        previous_frame = Some(value);
+                        frame
+                    } else {
+                        state
+                    };
+                    vec![frame]
+                };
+                let frames = match sampler.as_mut() {
+                    Some(sampler) => match sampler.offer(tick, frames) {
+                        Some(frames) => frames,
+                        None => return Ok(()),
+                    },
+                    None => frames,
+                };
+                debug_assert!(
+                    written_tick.is_none_or(|written| tick > written),
+                    "GameState frames went from tick {written_tick:?} to {tick}"
+                );
+                written_tick = Some(tick);
+                for frame in frames {
+                    writer.write_frame(frame)?;
+                }
+                manifest.gamestate_frames += 1;
+                if let Some(timing) = timing.as_mut() {
+                    timing.frame(tick);
+                }
+            }
+            ParseEvent::Record(record) => {
+                if let StreamRecord::Round(round) = &record {
+                    if let Some(timing) = timing.as_mut() {
+                        timing.round(round);
+                    }
+                    if !args.dump_rounds {
+                        return Ok(());
+                    }
+                }
+                if let StreamRecord::MatchEvent(event) = &record {
+                    if let Some(timing) = timing.as_mut() {
+                        timing.match_event(event);
+                    }
+                    if !args.dump_match_events {
+                        return Ok(());
+                    }
+                }
+                if let StreamRecord::PlayerScore(score) = &record {
+                    if let Some(steamid) = &score.steamid {
+                        scores.insert(steamid.clone(), score.score);
+                    }
+                    if !args.dump_player_scores {
+                        return Ok(());
+                    }
+                }
+                let kind = record.kind();
+                if let Some(remaining) = remaining_records.get_mut(&kind) {
+                    let Some(left) = remaining.checked_sub(1) else {
+                        return Ok(());
+                    };
+                    *remaining = left;
+                    if stop_when_capped && remaining_records.values().all(|&left| left == 0) {
+                        tracing::info!("Every capped stream is full, stopping.");
+                        capped.store(true, Ordering::Relaxed);
+                    }
+                }
+                manifest.count_record(kind.name());
+                writer.write_record(record)?;
+            }
+            ParseEvent::PacketError {
+                tick,
+                error,
+                bit_position,
+                skipped_bits,
+            } => {
+                // Through the log rather than straight to a stream, so it respects `RUST_LOG`,
+                // lands in the log file and stays out of stdout output
+                if args.verbose_errors {
+                    tracing::warn!(
+                        error = ?error,
+                        tick,
+                        bit_position,
+                        skipped_bits,
+                        "recoverable packet error"
+                    );
+                } else {
+                    tracing::debug!(
+                        error = ?error,
+                        tick,
+                        bit_position,
+                        skipped_bits,
+                        "recoverable packet error"
+                    );
+                }
+                errors.record(tick, &error, skipped_bits);
+            }
+            ParseEvent::PacketStats(counts) => manifest.packet_stats = Some(counts),
+            ParseEvent::Done { frames } => parsed_frames = frames,
+        }
+        Ok(())
+    };
+    let (result, file) = if args.follow {
+        let idle_timeout = Duration::from_secs(args.follow_timeout);
+        follow_demo(infile, file, &opts, idle_timeout, interrupted, &mut handle_event)
+    } else {
+        (parse_demo_with(&file, &opts, &mut handle_event), file)
+    };
+    if args.follow {
+        // The header of a recording is only filled in once it's finished
+        if let Ok(header) = &result {
+            manifest.header = Some(header.clone());
+        }
+    }
+    // Of the data as parsed, which a followed demo only has in full by now
+    if args.sha256 {
+        manifest.sha256 = Some(format!("{:x}", Sha256::digest(&file[..])));
+    }
+    if let Some(sampler) = sampler {
+        let (held, report) = sampler.finish();
+        for (tick, frames) in held {
+            for frame in frames {
+                writer.write_frame(frame)?;
+            }
+            manifest.gamestate_frames += 1;
+            if let Some(timing) = timing.as_mut() {
+                timing.frame(tick);
+            }
+        }
+        write_json(&sample_path, &report, args.fsync)?;
+        tracing::info!("Wrote the sampled ticks to {:?}.", &sample_path);
+    }
+    let (gsd_chunks, write_result) = writer.finish();
+    let tickrate = header_rate
+        .map(|header_rate| Tickrate::resolve(args.tickrate, server_rate, header_rate));
+    manifest.tickrate = tickrate;
+    if let (Some(timing), Some(tickrate)) = (timing, tickrate) {
+        write_json(&timing_path, &timing.finish(tickrate), args.fsync)?;
+        tracing::info!("Wrote timing to {:?}.", &timing_path);
+    }
+    if let Some(index) = index {
+        write_json(&index_path, &index, args.fsync)?;
+        tracing::info!("Wrote offset index to {:?}.", &index_path);
+    }
+    if rotated {
+        manifest.gsd_chunks = Some(gsd_chunks);
+    }
+    if let Some(status_file) = status_file.as_mut() {
+        let mut status = Status::new(
+            demo_name,
+            last_tick,
+            total_ticks,
+            progress.ticks(),
+            progress.elapsed(),
+        );
+        status.errors = errors.total();
+        status.done = true;
+        status_file.write(&status);
+    }
+    let throughput = Throughput::new(progress.ticks(), file.len() as u64, progress.elapsed());
+    manifest.throughput = Some(throughput);
+    let stopped_early = args.max_ticks.is_some()
+        || capped.load(Ordering::Relaxed)
+        || interrupted.load(Ordering::Relaxed);
+    // Stopping early on purpose leaves the demo unfinished too, that's not the demo's fault, and
+    // a fragment has no header to compare with
+    if !stopped_early && !args.no_header {
+        if let Some(header) = &manifest.header {
+            manifest.truncated =
+                check_truncation(header, progress.ticks(), bytes_read, file.len() as u64);
+        }
+    }
+    // Written last so the counts are accurate even when the run ended early
+    let completed =
+        result.is_ok() && write_result.is_ok() && !interrupted.load(Ordering::Relaxed);
+    manifest.finish(completed);
+    manifest.write(&manifest_path, args.fsync)?;
+    // Reported either way, the errors leading up to lost sync are the interesting part
+    errors.report();
+    // A failed write also stops the parse, so its error takes priority
+    write_result?;
+    result?;
+    progress.finish();
+    tracing::info!(
+        "Parsed {} ticks from {} bytes in {:.2}s ({:.0} ticks/s, {:.2} MB/s).",
+        throughput.ticks,
+        throughput.input_bytes,
+        throughput.elapsed_secs,
+        throughput.ticks_per_sec,
+        throughput.mb_per_sec
+    );
+    if let Some(counts) = &manifest.packet_stats {
+        log_packet_stats(counts);
+    }
+    if let Some(filter) = &player_filter {
+        if args.listed_players.is_empty() {
+            for player in filter.unmatched() {
+                tracing::warn!("Player '{player}' from --players never appeared in the demo.");
+            }
+        } else {
+            // A watchlist mostly won't appear, a warning for each would drown out the rest
+            let unmatched = filter.unmatched().count();
+            tracing::info!(
+                "{} of the {} requested players appeared in the demo.",
+                filter.len() - unmatched,
+                filter.len()
+            );
+        }
+    }
+    if let Some(filter) = &raw_filter {
+        for packet_type in filter.unmatched() {
+            tracing::warn!("No packet matched '{packet_type}' from --raw-filter.");
+        }
+    }
+    // Stopping early can't match the header, that's not the parser's fault
+    if args.strict && !stopped_early {
+        if let Some(header) = &manifest.header {
+            check_counts(header, parsed_frames, progress.ticks())?;
+        }
+    }
+    if args.verify_output {
+        let gsd_files = manifest
+            .gsd_chunks
+            .clone()
+            .unwrap_or_else(|| vec![gs_path.clone()]);
+        verify_frames(
+            &gsd_files,
+            args.format,
+            args.compress,
+            manifest.gamestate_frames,
+        )?;
+    }
+    tracing::info!("Demo packet parsing of {infile} succeeded.");
+    Ok(())
+}
+
+/// Where the GameState delta output goes.
+enum GsdOutput<'a> {
+    /// A single file, or stdout without a path.
+    Single(Option<&'a Path>),
+    Chunks(Rotation, ChunkPaths),
+    /// `--no-gamestate-file`, nothing is written.
+    Skipped,
+}
+
+/// Opens the GameState delta and raw packet outputs with the sink for `format`, and starts
+/// writing everything on its own thread.
+fn spawn_writer<S: RecordSink + 'static>(
+    open_sink: fn(OutputStream) -> S,
+    format: OutputFormat,
+    options: &OutputOptions,
+    gsd_output: GsdOutput<'_>,
+    raw_path: Option<&Path>,
+    stream_writers: HashMap<StreamKind, StreamSink>,
+) -> io::Result<WriterThread> {
+    let gsd_writer = match gsd_output {
+        GsdOutput::Chunks(rotation, chunk_paths) => {
+            tracing::info!(
+                "Generated GameStateDelta serialiser rotating over chunks from {:?}.",
+                chunk_paths.path(0)
+            );
+            Some(FrameWriter::Rotating(RotatingWriter::new(
+                format,
+                *options,
+                rotation,
+                chunk_paths,
+                open_sink,
+            )))
+        }
+        GsdOutput::Single(gsd_target) => {
+            let writer = open_sink(open_output(gsd_target, format, options)?);
+            tracing::info!(
+                "Generated GameStateDelta serialiser with target {:?}.",
+                gsd_target.unwrap_or(Path::new("<stdout>"))
+            );
+            Some(FrameWriter::Single(writer))
+        }
+        GsdOutput::Skipped => None,
+    };
+    // Raw packets output file, only created when raw dumping is enabled
+    let raw_writer = match raw_path {
+        Some(raw_path) => {
+            let writer = open_sink(open_output(Some(raw_path), format, options)?);
+            tracing::info!("Generated raw serialiser with file {:?}.", raw_path);
+            Some(writer)
+        }
+        None => None,
+    };
+    Ok(WriterThread::spawn(gsd_writer, raw_writer, stream_writers))
+}
+
+/// Resolves `--name-template` into the path of each output of a demo.
+struct OutputNames<'a> {
+    outpath: &'a Path,
+    template: &'a str,
+    stem: &'a str,
+    map: String,
+    ticks: String,
+}
+
+impl<'a> OutputNames<'a> {
+    /// Whether the template refers to anything that has to be read from the header.
+    fn needs_header(template: &str) -> bool {
+        template.contains("{map}") || template.contains("{ticks}")
+    }
+
+    fn new(outpath: &'a Path, template: &'a str, stem: &'a str, header: Option<&Header>) -> Self {
+        // Workshop maps are named like `workshop/cp_foo.ugc123`, which shouldn't become a directory
+        let map = header.map_or_else(String::new, |header| {
+            header
+                .map
+                .trim_end_matches('\0')
+                .replace(['/', '\\'], "_")
+        });
+        OutputNames {
+            outpath,
+            template,
+            stem,
+            map,
+            ticks: header.map_or_else(String::new, |header| header.ticks.to_string()),
+        }
+    }
+
+    /// The path of one kind of output, e.g. `gsd` with the `msgpack.gz` extension.
+    fn path(&self, kind: &str, extension: &str) -> PathBuf {
+        let name = self
+            .template
+            .replace("{stem}", self.stem)
+            .replace("{map}", &self.map)
+            .replace("{ticks}", &self.ticks)
+            .replace("{kind}", kind);
+        self.outpath.join(format!("{name}.{extension}"))
+    }
+}
+
+/// Runs the packet loop over a demo for `--validate`, without creating any output.
+fn validate_demo(
+    args: &Args,
+    infile: &str,
+    file: &[u8],
+    demo_name: &str,
+    multi: &MultiProgress,
+    interrupted: &Arc<AtomicBool>,
+) -> Result<(), MainError> {
+    let progress = new_progress(args, multi, demo_name);
+    // The state still has to be tracked to parse the packets, it just isn't handed out
+    let opts = ParseOptions {
+        parse_raw: false,
+        parse_gamestate: false,
+        max_ticks: args.max_ticks,
+        max_consecutive_errors: args.max_consecutive_errors,
+        max_total_errors: args.max_errors,
+        stop: vec![Arc::clone(interrupted)],
+        protocol_compat: args.protocol_compat,
+        ..ParseOptions::default()
+    };
+    let mut errors = ErrorSummary::default();
+    let mut header = None;
+    let mut bytes_read = 0;
+    tracing::info!("Validating demo...");
+    parse_demo_with(file, &opts, |event| {
+        match event {
+            ParseEvent::Header(demo_header) => {
+                let total = args
+                    .max_ticks
+                    .map_or(demo_header.ticks, |max_ticks| demo_header.ticks.min(max_ticks));
+                progress.start(total as u64, file.len() as u64);
+                header = Some(demo_header.clone());
+            }
+            ParseEvent::Tick {
+                bytes_read: read, ..
+            } => {
+                progress.tick(read);
+                bytes_read = read;
+            }
+            ParseEvent::PacketError {
+                tick,
+                error,
+                skipped_bits,
+                ..
+            } => errors.record(tick, &error, skipped_bits),
+            _ => {}
+        }
+        Ok(())
+    })
+    .inspect_err(|_| errors.report())?;
+    progress.finish();
+    errors.report();
+    if errors.total() > 0 {
+        return Err(format!(
+            "{infile} parsed with {} recovered packet errors.",
+            errors.total()
+        )
+        .into());
+    }
+    let stopped_early = args.max_ticks.is_some() || interrupted.load(Ordering::Relaxed);
+    if let (Some(header), false) = (&header, stopped_early) {
+        if check_truncation(header, progress.ticks(), bytes_read, file.len() as u64) {
+            return Err(format!("{infile} is truncated.").into());
+        }
+    }
+    tracing::info!("{infile} is valid.");
+    Ok(())
+}
+
+/// How many ticks `--list-streams` looks at, about five minutes at 66 ticks per second.
+const LIST_SAMPLE_TICKS: u64 = 20_000;
+
+/// Runs every side stream over the start of a demo for `--list-streams`, printing how many
+/// records each produced. Streams that only show up later in a match, like ubercharges, can be
+/// missed.
+fn list_streams(
+    args: &Args,
+    file: &[u8],
+    demo_name: &str,
+    multi: &MultiProgress,
+    interrupted: &Arc<AtomicBool>,
+) -> Result<(), MainError> {
+    let progress = new_progress(args, multi, demo_name);
+    // Its own flag, so stopping after the sample doesn't look like an interrupt to other demos
+    let sampled = Arc::new(AtomicBool::new(false));
+    let opts = ParseOptions {
+        parse_raw: false,
+        parse_gamestate: false,
+        streams: StreamKind::ALL.to_vec(),
+        max_consecutive_errors: args.max_consecutive_errors,
+        max_total_errors: args.max_errors,
+        stop: vec![Arc::clone(interrupted), Arc::clone(&sampled)],
+        protocol_compat: args.protocol_compat,
+        ..ParseOptions::default()
+    };
+    let mut header = None;
+    let mut ticks = 0;
+    let mut records: BTreeMap<StreamKind, u64> = BTreeMap::new();
+    tracing::info!("Sampling streams...");
+    parse_demo_with(file, &opts, |event| {
+        match event {
+            ParseEvent::Header(demo_header) => {
+                progress.start(
+                    u64::from(demo_header.ticks).min(LIST_SAMPLE_TICKS),
+                    file.len() as u64,
+                );
+                header = Some(demo_header.clone());
+            }
+            ParseEvent::Tick {
+                bytes_read: read, ..
+            } => {
+                progress.tick(read);
+                ticks += 1;
+                if ticks >= LIST_SAMPLE_TICKS {
+                    sampled.store(true, Ordering::Relaxed);
+                }
+            }
+            ParseEvent::Record(record) => *records.entry(record.kind()).or_default() += 1,
+            _ => {}
+        }
+        Ok(())
+    })?;
+    progress.finish();
+    if let Some(header) = &header {
+        println!(
+            "{demo_name}: {} on {}, {} ticks ({:.0}s), sampled {ticks}",
+            header.map, header.server, header.ticks, header.duration
         );
     }
+    // One summary per player, whenever they were on the server
+    let players = records.get(&StreamKind::Players).copied().unwrap_or_default();
+    println!("  {players} players");
+    for &kind in StreamKind::ALL {
+        let count = records.get(&kind).copied().unwrap_or_default();
+        let found = if count > 0 {
+            format!("{count} records")
+        } else {
+            "none found".to_string()
+        };
+        println!("  {:<16} {:<24} {found}", kind.name(), stream_flag(kind));
+    }
+    Ok(())
+}
+
+/// The records sampled for one output by `--estimate`.
+#[derive(Default)]
+struct SizeSample {
+    records: u64,
+    bytes: u64,
+}
+
+impl SizeSample {
+    fn add(&mut self, encoded: Result<Vec<u8>, Error>) -> Result<(), Error> {
+        self.records += 1;
+        self.bytes += encoded?.len() as u64;
+        Ok(())
+    }
+}
 
-    tracing::info!("Reading provided input demo...");
-    let file = fs::read(&args.infile)?;
-
-    tracing::info!("Instantiating Demo Handler...");
-    let demo = Demo::new(&file);
-    let mut handler = DemoHandler::with_analyser(GameStateAnalyser::new());
-
-    tracing::info!("Handling the demo header...");
-    let mut stream = demo.get_stream();
-    let header = Header::read(&mut stream).unwrap();
-    handler.handle_header(&header);
-    tracing::info!("Success! Preparing to handle packet stream...");
-    let total = header.ticks;
-
-    // A Vector of json-serialised gamestate strings
-    let mut packet_stream: RawPacketStream = RawPacketStream::new(stream);
-    let mut current_tick: u32 = 0;
-
-    tracing::info!("Generating msgpack serialisers...");
-    let demo_name = args.infile.split_once(".dem").unwrap().0;
-    let path: PathBuf = PathBuf::from_str(args.outpath.as_str()).expect("Couldn't convert outpath to path");
-    let gs_path = path.join(format!("{demo_name}-gsd.msgpack"));
-    let raw_path = path.join(format!("{demo_name}-raw.msgpack"));
-    
-
-    // GameState Delta output msgpack file
-    let gsd_outfile = File::create(gs_path).expect("Couldn't create output file.");
-    let gsd_file_bufwriter = BufWriter::new(&gsd_outfile);
-    let mut gsd_msgpack_serialiser = Serializer::new(gsd_file_bufwriter);
-    tracing::info!("Generated GameStateDelta serialiser with file {:?}.", &gsd_outfile);
-    // Raw packets output msgpack file
-    let raw_outfile = File::create(&raw_path).expect("Couldn't create output file.");
-    let raw_file_bufwriter = BufWriter::new(raw_outfile);
-    let mut raw_msgpack_serialiser = Serializer::new(raw_file_bufwriter);
+/// Encodes the outputs chosen by the other flags over the start of a demo for `--estimate`, and
+/// prints how large each would come out for the whole demo. Records are assumed to come at the
+/// same rate for the rest of it, other than the one-shot documents written at the end.
+fn estimate_output(
+    args: &Args,
+    file: &[u8],
+    demo_name: &str,
+    multi: &MultiProgress,
+    interrupted: &Arc<AtomicBool>,
+) -> Result<(), MainError> {
+    let progress = new_progress(args, multi, demo_name);
+    let sampled = Arc::new(AtomicBool::new(false));
+    let gamestate = !args.dont_parse_gamestate && !args.no_gamestate_file;
+    let opts = ParseOptions {
+        parse_raw: args.parse_raw,
+        parse_gamestate: gamestate,
+        tick_range: args
+            .tick_range
+            .or(args.start_tick.map(|start_tick| (start_tick, u32::MAX))),
+        max_ticks: args.max_ticks,
+        streams: requested_streams(args),
+        stream_options: stream_options(args),
+        max_consecutive_errors: args.max_consecutive_errors,
+        max_total_errors: args.max_errors,
+        stop: vec![Arc::clone(interrupted), Arc::clone(&sampled)],
+        protocol_compat: args.protocol_compat,
+        ..ParseOptions::default()
+    };
+    let mut header = None;
+    let mut ticks = 0;
+    let mut gsd = SizeSample::default();
+    let mut raw = SizeSample::default();
+    let mut records: BTreeMap<StreamKind, SizeSample> = BTreeMap::new();
+    tracing::info!("Sampling output sizes...");
+    parse_demo_with(file, &opts, |event| {
+        match event {
+            ParseEvent::Header(demo_header) => {
+                progress.start(
+                    u64::from(demo_header.ticks).min(LIST_SAMPLE_TICKS),
+                    file.len() as u64,
+                );
+                header = Some(demo_header.clone());
+            }
+            ParseEvent::Tick {
+                bytes_read: read, ..
+            } => {
+                progress.tick(read);
+                ticks += 1;
+                if ticks >= LIST_SAMPLE_TICKS {
+                    sampled.store(true, Ordering::Relaxed);
+                }
+            }
+            ParseEvent::GameState(state) => gsd.add(args.format.encode(state))?,
+            ParseEvent::RawPacket(packet) => raw.add(args.format.encode(packet))?,
+            // Every side stream is written as JSON, a line per record or as one document
+            ParseEvent::Record(record) => records
+                .entry(record.kind())
+                .or_default()
+                .add(OutputFormat::Jsonl.encode(&record))?,
+            _ => {}
+        }
+        Ok(())
+    })?;
+    progress.finish();
+    let Some(header) = header else {
+        return Ok(());
+    };
+    let total_ticks = match args.max_ticks {
+        Some(max_ticks) => header.ticks.min(max_ticks),
+        None => header.ticks,
+    };
+    // A parse that got through the whole demo needs no extrapolating
+    let scale = if sampled.load(Ordering::Relaxed) && ticks > 0 {
+        f64::from(total_ticks) / ticks as f64
+    } else {
+        1.0
+    };
+    println!(
+        "{demo_name}: {} ticks, sampled {ticks}, as {:?} before compression",
+        header.ticks, args.format
+    );
+    let mut outputs = Vec::new();
+    if gamestate {
+        outputs.push(("gsd", gsd, scale));
+    }
     if args.parse_raw {
-        tracing::info!("Generated raw serialiser with file {:?}.", &gsd_outfile);
+        outputs.push(("raw", raw, scale));
+    }
+    for kind in requested_streams(args) {
+        let sample = records.remove(&kind).unwrap_or_default();
+        outputs.push((kind.name(), sample, if kind.is_document() { 1.0 } else { scale }));
+    }
+    let mut total = 0;
+    for (name, sample, scale) in outputs {
+        let records = (sample.records as f64 * scale).round() as u64;
+        let bytes = (sample.bytes as f64 * scale).round() as u64;
+        total += bytes;
+        println!("  {name:<16} {records:>12} records {:>12}", format_size(bytes));
+    }
+    println!("  {:<16} {:>20} {:>12}", "total", "", format_size(total));
+    Ok(())
+}
+
+/// A byte count with a binary unit, e.g. `1.5 MiB`.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1 << 10 {
+        return format!("{bytes} B");
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for next in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next;
+    }
+    format!("{size:.1} {unit}")
+}
+
+/// The progress for one demo, drawn in the `--progress-style` when stderr is a terminal. Only the
+/// plain style is written to other stderrs, which otherwise just get the occasional log line.
+fn new_progress(args: &Args, multi: &MultiProgress, label: &str) -> Progress {
+    let style = if args.no_progress || args.quiet {
+        None
     } else {
-        fs::remove_file(&raw_path).expect("Couldn't delete newly created but unneeded file.");
+        match args.progress_style {
+            Some(BarStyle::Plain) => Some(BarStyle::Plain),
+            _ if !io::stderr().is_terminal() => None,
+            Some(style) => Some(style),
+            None => Some(BarStyle::detect()),
+        }
+    };
+    Progress::new(multi, args.progress_mode, style, label)
+}
+
+/// Logs the `--packet-stats` histogram, most common types first.
+fn log_packet_stats(counts: &PacketCounts) {
+    let sections = [
+        ("Packets", &counts.packets),
+        ("Messages", &counts.messages),
+        ("User messages", &counts.user_messages),
+    ];
+    for (title, counts) in sections {
+        let mut counts: Vec<_> = counts.iter().collect();
+        counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        let total: u64 = counts.iter().map(|(_, count)| **count).sum();
+        tracing::info!("{title}: {total}");
+        for (name, count) in counts {
+            tracing::info!("  {name:<24} {count:>10}");
+        }
     }
+}
 
-    tracing::info!("Parsing demo packets...");
-    let bar = ProgressBar::new(total as u64);
-    let tick_strs = [
-        "⢀⠀", "⡀⠀", "⠄⠀", "⢂⠀", "⡂⠀", "⠅⠀", "⢃⠀", "⡃⠀", "⠍⠀", "⢋⠀", "⡋⠀", "⠍⠁", "⢋⠁", "⡋⠁", "⠍⠉",
-        "⠋⠉", "⠋⠉", "⠉⠙", "⠉⠙", "⠉⠩", "⠈⢙", "⠈⡙", "⢈⠩", "⡀⢙", "⠄⡙", "⢂⠩", "⡂⢘", "⠅⡘", "⢃⠨", "⡃⢐",
-        "⠍⡐", "⢋⠠", "⡋⢀", "⠍⡁", "⢋⠁", "⡋⠁", "⠍⠉", "⠋⠉", "⠋⠉", "⠉⠙", "⠉⠙", "⠉⠩", "⠈⢙", "⠈⡙", "⠈⠩",
-        "⠀⢙", "⠀⡙", "⠀⠩", "⠀⢘", "⠀⡘", "⠀⠨", "⠀⢐", "⠀⡐", "⠀⠠", "⠀⢀", "⠀⡀",
+/// The flag that writes a side stream.
+fn stream_flag(kind: StreamKind) -> &'static str {
+    match kind {
+        StreamKind::Chat => "--dump-chat",
+        StreamKind::Deaths => "--dump-deaths",
+        StreamKind::Positions => "--dump-positions",
+        StreamKind::Rounds => "--dump-rounds",
+        StreamKind::Loadouts => "--dump-loadouts",
+        StreamKind::VoiceActivity => "--dump-voice-activity",
+        StreamKind::Scoreboard => "--dump-scoreboard",
+        StreamKind::CombatLog => "--dump-combat-log",
+        StreamKind::CombatSummary => "--combat-summary",
+        StreamKind::Projectiles => "--dump-projectiles",
+        StreamKind::ClassChanges => "--dump-class-changes",
+        StreamKind::Ubercharge => "--dump-ubercharge",
+        StreamKind::EntityEvents => "--dump-entity-events",
+        StreamKind::PlayerScores => "--dump-player-scores",
+        StreamKind::Buildings => "--dump-buildings",
+        StreamKind::Players => "--player-summary",
+        StreamKind::Recorder => "--dump-recorder",
+        StreamKind::TeamChanges => "--dump-team-changes",
+        StreamKind::MatchEvents => "--dump-match-events",
+        StreamKind::Disconnects => "--dump-disconnects",
+        StreamKind::Items => "--dump-items",
+        StreamKind::Angles => "--dump-angles",
+        StreamKind::StringTables => "--dump-stringtables",
+        StreamKind::RespawnTiming => "--dump-respawn-timing",
+        StreamKind::FlagEvents => "--dump-flag-events",
+        StreamKind::ServerInfo => "--dump-serverinfo",
+    }
+}
+
+/// Parsing less than this share of the ticks or bytes the demo claims means it was cut short.
+const TRUNCATION_THRESHOLD: f64 = 0.99;
+
+/// Compares how much of the demo was parsed with what its header and size claim, warning if
+/// the recording looks like it was cut short.
+fn check_truncation(header: &Header, ticks_parsed: u64, bytes_read: u64, file_len: u64) -> bool {
+    if header.ticks == 0 {
+        // The header is only filled in once the recording stops, a crashed client leaves it empty
+        tracing::warn!(
+            "Demo appears truncated: its header was never finalised, parsed {ticks_parsed} ticks."
+        );
+        true
+    } else if (ticks_parsed as f64) < f64::from(header.ticks) * TRUNCATION_THRESHOLD {
+        tracing::warn!(
+            "Demo appears truncated: parsed {ticks_parsed} of {} ticks.",
+            header.ticks
+        );
+        true
+    } else if (bytes_read as f64) < file_len as f64 * TRUNCATION_THRESHOLD {
+        tracing::warn!("Demo appears truncated: parsed {bytes_read} of {file_len} bytes.");
+        true
+    } else {
+        false
+    }
+}
+
+/// How far the parsed counts may be off from the header's with `--strict`.
+const STRICT_TOLERANCE: f64 = 0.01;
+
+/// Compares the parsed frame and tick counts with the header's, printing each that's off.
+fn check_counts(header: &Header, frames: u64, ticks: u64) -> Result<(), String> {
+    let counts = [
+        ("frames", u64::from(header.frames), frames),
+        ("ticks", u64::from(header.ticks), ticks),
     ];
-    let bar_style_template = ProgressStyle::with_template(
-        "{spinner:.green} [{elapsed_precise}] [{bar:.green}] {msg} ({eta})",
-    )
-    .unwrap()
-    .with_key("eta", |state: &ProgressState, w: &mut dyn Write| {
-        write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
-    })
-    .progress_chars("█▉▊▋▌▍▎▏  ")
-    .tick_strings(&tick_strs);
-    bar.set_style(bar_style_template.clone());
-    bar.set_message("Parsing demo ticks...");
-    bar.enable_steady_tick(Duration::from_millis(25));
-    loop {
-        match packet_stream.next(&handler.state_handler) {
-            Ok(Some(packet)) => {
-                if args.parse_raw {
-                    packet.clone().serialize(&mut raw_msgpack_serialiser).expect("Couldn't serialise raw packet");
-                }
-
-                handler
-                    .handle_packet(packet)
-                    .expect("Couldn't handle packet.");
-
-                if !args.dont_parse_gamestate && handler.server_tick != current_tick {
-                    bar.inc(1);
-                    // print!("updating gamestate!!! 😂");
-                    let output: &GameState = handler.borrow_output();
-                    output.serialize(&mut gsd_msgpack_serialiser).expect("Couldn't serialise game state delta");
-                }
-                current_tick = handler.server_tick.into();
-            }
-            Ok(None) => break,
-            Err(e) => {
-                // We want to pull as much data as possible, even if this packet is corrupted
-                // Continue the stream and see if we can't recover.
-                println!("{:?}", e);
-                packet_stream.ended = false;
-                packet_stream.incomplete = false;
-            }
-        }
-    }
-    bar.finish_with_message("Demo parsed.");
-    tracing::info!("Demo packet parsing succeeded.");
+    let mut mismatched = 0;
+    for (name, expected, parsed) in counts {
+        if parsed.abs_diff(expected) as f64 > expected as f64 * STRICT_TOLERANCE {
+            let diff = parsed as i64 - expected as i64;
+            tracing::error!(
+                "The header says {expected} {name} but {parsed} were parsed, {diff:+} ({:+.1}%).",
+                diff as f64 / expected.max(1) as f64 * 100.0
+            );
+            mismatched += 1;
+        }
+    }
+    if mismatched > 0 {
+        return Err(format!("{mismatched} counts don't match the header."));
+    }
     Ok(())
 }
 
-fn init_tracing() -> Option<WorkerGuard> {
+/// Whether a previous run produced this output. An empty file means that run was interrupted.
+fn is_done(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|metadata| metadata.len() > 0)
+}
+
+/// Whether the path ends in `.dem`, in any case since demos copied off Windows can be `.DEM`.
+fn has_demo_extension(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("dem"))
+}
+
+/// Whether the demo was recorded on a map matching a `--map-filter` pattern, going by its header
+/// alone. A demo whose header can't be read is kept, so parsing it reports why.
+fn on_matching_map(infile: &str, pattern: &str) -> bool {
+    let header = read_head(infile)
+        .map_err(|e| e.to_string())
+        .and_then(|head| read_header(&head).map_err(|e| e.to_string()));
+    let header = match header {
+        Ok(header) => header,
+        Err(e) => {
+            tracing::debug!("Couldn't read the header of {infile} to filter it by map: {e}");
+            return true;
+        }
+    };
+    let map = header.map.to_ascii_lowercase();
+    let pattern = pattern.to_ascii_lowercase();
+    let matched = if pattern.contains(['*', '?']) {
+        glob_matches(pattern.as_bytes(), map.as_bytes())
+    } else {
+        map.contains(&pattern)
+    };
+    if !matched {
+        tracing::debug!("Skipping {infile}, recorded on {}.", header.map);
+    }
+    matched
+}
+
+/// Matches `text` against a pattern where `*` stands for any run of characters and `?` for any
+/// single one.
+fn glob_matches(pattern: &[u8], text: &[u8]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // Where to pick up again if the rest fails to match, with the last `*` taking one more byte
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, t));
+                p += 1;
+            }
+            Some(&c) if c == b'?' || c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// The demo's file name without directories or extension, e.g. `foo` for `/matches/foo.dem`, or
+/// the whole file name if it has no extension. Demos in an archive are named after their member,
+/// or the archive when it isn't given.
+fn demo_stem(infile: &str) -> Result<&str, String> {
+    if let Some(archived) = ArchivedDemo::parse(infile) {
+        let name = archived.member.unwrap_or(archived.archive);
+        return Path::new(name)
+            .file_stem()
+            .and_then(OsStr::to_str)
+            .ok_or_else(|| format!("Couldn't derive an output name from '{infile}'."));
+    }
+    let path = Path::new(infile);
+    if path.extension().is_some() && !has_demo_extension(path) {
+        return Err(format!(
+            "'{infile}' doesn't have a .dem extension, pass --name to choose the output name."
+        ));
+    }
+    path.file_stem()
+        .and_then(OsStr::to_str)
+        .ok_or_else(|| format!("Couldn't derive an output name from '{infile}'."))
+}
+
+/// Recovered packet errors, grouped by the error variant.
+#[derive(Default)]
+struct ErrorSummary {
+    ticks_by_kind: BTreeMap<String, Vec<u32>>,
+    /// The demo data skipped over while recovering, which never made it into the output.
+    skipped_bits: usize,
+}
+
+impl ErrorSummary {
+    fn record(&mut self, tick: u32, error: &ParseError, skipped_bits: usize) {
+        self.ticks_by_kind
+            .entry(error_kind(error))
+            .or_default()
+            .push(tick);
+        self.skipped_bits += skipped_bits;
+    }
+
+    fn total(&self) -> usize {
+        self.ticks_by_kind.values().map(Vec::len).sum()
+    }
+
+    fn report(&self) {
+        if self.ticks_by_kind.is_empty() {
+            return;
+        }
+        tracing::warn!(
+            "Recovered from {} packet errors across {} distinct kinds, skipping {} bytes of the demo.",
+            self.total(),
+            self.ticks_by_kind.len(),
+            self.skipped_bits.div_ceil(8)
+        );
+        for (kind, ticks) in &self.ticks_by_kind {
+            tracing::warn!("  {kind}: {} at ticks {:?}", ticks.len(), ticks);
+        }
+    }
+}
+
+/// The variant name of a parse error, e.g. `UnknownPacketType`.
+fn error_kind(error: &ParseError) -> String {
+    let debug = format!("{error:?}");
+    debug
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// A log file in the temp directory, named uniquely so concurrent runs don't clobber each other.
+fn default_log_file() -> PathBuf {
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs());
+    std::env::temp_dir().join(format!(
+        "minidemo_parser-{started_at}-{}.log",
+        process::id()
+    ))
+}
+
+/// The stderr log filter used when `RUST_LOG` isn't set, from `--quiet` and `--verbose`.
+fn log_filter(args: &Args) -> &'static str {
+    match (args.quiet, args.verbose) {
+        (true, _) => "error",
+        (false, 0) => "info,hyper::proto=warn",
+        (false, 1) => "debug",
+        (false, _) => "trace",
+    }
+}
+
+fn init_tracing(log_file: &Path, default_filter: &str, json: bool) -> Option<WorkerGuard> {
     if std::env::var("RUST_LOG").is_err() {
-        std::env::set_var("RUST_LOG", "info,hyper::proto=warn");
+        std::env::set_var("RUST_LOG", default_filter);
     }
 
     let subscriber = tracing_subscriber::registry().with(
@@ -143,16 +2231,25 @@ fn init_tracing() -> Option<WorkerGuard> {
             .with_filter(EnvFilter::from_default_env()),
     );
 
-    match std::fs::File::create("./minidemo_parser.log") {
+    match std::fs::File::create(log_file) {
         Ok(latest_log) => {
             let (file_writer, guard) = tracing_appender::non_blocking(latest_log);
-            subscriber
-                .with(
-                    tracing_subscriber::fmt::layer()
-                        .with_ansi(false)
-                        .with_writer(file_writer.with_max_level(tracing::Level::TRACE)),
-                )
-                .init();
+            let file_writer = file_writer.with_max_level(tracing::Level::TRACE);
+            // Only one of the two is ever set, the formats are different types
+            let json_layer = json.then(|| {
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_current_span(true)
+                    .with_span_list(true)
+                    .with_writer(file_writer.clone())
+            });
+            let text_layer = (!json).then(|| {
+                tracing_subscriber::fmt::layer()
+                    .with_ansi(false)
+                    .with_writer(file_writer)
+            });
+            subscriber.with(json_layer).with(text_layer).init();
+            tracing::info!("Logging to {}.", log_file.display());
             Some(guard)
         }
         Err(e) => {
@@ -165,3 +2262,76 @@ fn init_tracing() -> Option<WorkerGuard> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tick_ranges() {
+        assert_eq!(parse_tick_range("100:200"), Ok((100, 200)));
+        assert_eq!(parse_tick_range(" 5 : 5 "), Ok((5, 5)));
+        assert!(parse_tick_range("200:100").is_err());
+        assert!(parse_tick_range("100").is_err());
+        assert!(parse_tick_range("-1:5").is_err());
+        assert!(parse_tick_range("1:4294967296").is_err());
+    }
+
+    #[test]
+    fn sizes() {
+        assert_eq!(parse_size("512"), Ok(512));
+        assert_eq!(parse_size("512B"), Ok(512));
+        assert_eq!(parse_size("4k"), Ok(4 << 10));
+        assert_eq!(parse_size("10 MiB"), Ok(10 << 20));
+        assert_eq!(parse_size("2G"), Ok(2 << 30));
+        assert!(parse_size("0").is_err());
+        assert!(parse_size("0M").is_err());
+        assert!(parse_size("").is_err());
+        assert!(parse_size("M").is_err());
+        assert!(parse_size("1.5M").is_err());
+        assert!(parse_size("3T").is_err());
+        assert!(parse_size("17179869184G").is_err());
+        assert!(parse_size("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn record_caps() {
+        assert_eq!(parse_record_cap("chat=10"), Ok((StreamKind::Chat, 10)));
+        assert_eq!(
+            parse_record_cap("combat-log = 3"),
+            Ok((StreamKind::CombatLog, 3))
+        );
+        assert!(parse_record_cap("chat").is_err());
+        assert!(parse_record_cap("chat=-1").is_err());
+        assert!(parse_record_cap("Chat=1").is_err());
+        assert!(parse_record_cap("nonsense=1").is_err());
+    }
+
+    #[test]
+    fn tickrates() {
+        assert_eq!(parse_tickrate("66.67"), Ok(66.67));
+        assert!(parse_tickrate("0").is_err());
+        assert!(parse_tickrate("-66").is_err());
+        assert!(parse_tickrate("inf").is_err());
+        assert!(parse_tickrate("NaN").is_err());
+    }
+
+    #[test]
+    fn globs() {
+        let matches = |pattern: &str, text: &str| glob_matches(pattern.as_bytes(), text.as_bytes());
+        assert!(matches("*.dem", "match.dem"));
+        assert!(matches("*.dem", ".dem"));
+        assert!(!matches("*.dem", "match.dem.zst"));
+        assert!(matches("round?.dem", "round1.dem"));
+        assert!(!matches("round?.dem", "round.dem"));
+        assert!(!matches("round?.dem", "round10.dem"));
+        // The `*` has to give back what it took once the rest stops matching
+        assert!(matches("*ab", "aab"));
+        assert!(matches("a*b*c", "abxbybc"));
+        assert!(!matches("a*b*c", "abxbyb"));
+        assert!(matches("**", ""));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+        assert!(!matches("a*", ""));
+    }
+}
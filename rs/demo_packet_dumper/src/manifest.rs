@@ -0,0 +1,127 @@
+use crate::{output::write_json, timing::Tickrate};
+use demo_packet_dumper::stats::PacketCounts;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fs, io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tf_demo_parser::demo::header::Header;
+
+/// Describes a dump run, written next to the outputs so they're self-describing.
+#[derive(Debug, Serialize)]
+pub struct Manifest {
+    pub tool_version: &'static str,
+    pub demo: String,
+    /// The demo's SHA-256 as hex, with `--sha256`.
+    pub sha256: Option<String>,
+    /// `None` if the header couldn't be read.
+    pub header: Option<Header>,
+    /// The tick rate ticks convert to seconds at, `None` if the header couldn't be read.
+    pub tickrate: Option<Tickrate>,
+    /// Unix timestamps, in seconds.
+    pub started_at: u64,
+    pub finished_at: u64,
+    /// Whether the whole demo was parsed and written without a fatal error.
+    pub completed: bool,
+    /// The demo ended well before its header or file size says it should, e.g. because the
+    /// recording client crashed.
+    pub truncated: bool,
+    /// The outputs that were produced, e.g. `gsd`, `raw` or `chat`.
+    pub streams: Vec<&'static str>,
+    pub gamestate_frames: u64,
+    pub raw_packets: u64,
+    /// The files the GameState output was split over with `--rotate-bytes` or `--rotate-frames`,
+    /// in order.
+    pub gsd_chunks: Option<Vec<PathBuf>>,
+    pub stream_records: BTreeMap<&'static str, u64>,
+    pub throughput: Option<Throughput>,
+    /// How many of each packet type the demo had, with `--packet-stats`.
+    pub packet_stats: Option<PacketCounts>,
+    /// Where the log for this run was written, if anywhere.
+    pub log_file: Option<PathBuf>,
+}
+
+/// How fast the packet stream was parsed, for comparing parser versions.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Throughput {
+    pub ticks: u64,
+    pub input_bytes: u64,
+    pub elapsed_secs: f64,
+    pub ticks_per_sec: f64,
+    pub mb_per_sec: f64,
+}
+
+impl Throughput {
+    pub fn new(ticks: u64, input_bytes: u64, elapsed: Duration) -> Self {
+        let elapsed_secs = elapsed.as_secs_f64();
+        let per_sec = |amount: f64| {
+            if elapsed_secs > 0.0 {
+                amount / elapsed_secs
+            } else {
+                0.0
+            }
+        };
+        Throughput {
+            ticks,
+            input_bytes,
+            elapsed_secs,
+            ticks_per_sec: per_sec(ticks as f64),
+            mb_per_sec: per_sec(input_bytes as f64 / 1_000_000.0),
+        }
+    }
+}
+
+impl Manifest {
+    pub fn new(demo: &str, streams: Vec<&'static str>) -> Self {
+        Manifest {
+            tool_version: env!("CARGO_PKG_VERSION"),
+            demo: demo.to_string(),
+            sha256: None,
+            header: None,
+            tickrate: None,
+            started_at: unix_now(),
+            finished_at: 0,
+            completed: false,
+            truncated: false,
+            streams,
+            gamestate_frames: 0,
+            raw_packets: 0,
+            gsd_chunks: None,
+            stream_records: BTreeMap::new(),
+            throughput: None,
+            packet_stats: None,
+            log_file: None,
+        }
+    }
+
+    pub fn count_record(&mut self, stream: &'static str) {
+        *self.stream_records.entry(stream).or_default() += 1;
+    }
+
+    pub fn finish(&mut self, completed: bool) {
+        self.finished_at = unix_now();
+        self.completed = completed;
+    }
+
+    pub fn write(&self, path: &Path, fsync: bool) -> io::Result<()> {
+        write_json(path, self, fsync)
+    }
+}
+
+/// Whether the manifest at `path` is from a run that completed. It's also written when a run
+/// fails or is interrupted, so its existence alone doesn't say the demo is done.
+pub fn is_completed(path: &Path) -> bool {
+    fs::read(path)
+        .ok()
+        .and_then(|data| serde_json::from_slice::<serde_json::Value>(&data).ok())
+        .and_then(|manifest| manifest.get("completed")?.as_bool())
+        .unwrap_or(false)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| since_epoch.as_secs())
+}
@@ -0,0 +1,860 @@
+use ciborium::Value;
+use clap::ValueEnum;
+use demo_packet_dumper::{schema, Error};
+use rmp_serde::Serializer;
+use serde::Serialize;
+use std::{
+    collections::HashSet,
+    ffi::OsString,
+    fs::{self, File},
+    io::{self, BufWriter, Read, Stdout, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+/// Serialisation format used for the output streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Msgpack,
+    /// One JSON document per line.
+    Jsonl,
+    /// A CBOR sequence, one data item per record after a tagged version header.
+    Cbor,
+    /// A row per player per tick, for spreadsheets. Only the GameState output can be flattened.
+    Csv,
+    /// A single JSON document of every record, used for the one-shot artifacts.
+    #[value(skip)]
+    Json,
+}
+
+impl OutputFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            OutputFormat::Msgpack => "msgpack",
+            OutputFormat::Jsonl => "jsonl",
+            OutputFormat::Cbor => "cbor",
+            OutputFormat::Csv => "csv",
+            OutputFormat::Json => "json",
+        }
+    }
+
+    /// Encodes a single record up front, for when it can't be handed to a `RecordSink` directly.
+    pub fn encode<T: Serialize + ?Sized>(self, record: &T) -> Result<Vec<u8>, Error> {
+        match self {
+            OutputFormat::Msgpack => rmp_serde::to_vec(record).map_err(Error::output),
+            OutputFormat::Jsonl => {
+                let mut line = serde_json::to_vec(record).map_err(Error::output)?;
+                line.push(b'\n');
+                Ok(line)
+            }
+            OutputFormat::Cbor => {
+                let mut item = Vec::new();
+                ciborium::into_writer(record, &mut item).map_err(Error::output)?;
+                Ok(item)
+            }
+            OutputFormat::Csv => {
+                let mut row = csv::WriterBuilder::new()
+                    .has_headers(false)
+                    .from_writer(Vec::new());
+                row.serialize(record).map_err(Error::output)?;
+                row.into_inner().map_err(|e| Error::output(e.into_error()))
+            }
+            OutputFormat::Json => serde_json::to_vec(record).map_err(Error::output),
+        }
+    }
+}
+
+/// Compression applied to the output files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+impl Compression {
+    /// Suffix appended after the format extension, e.g. `.gz` in `.msgpack.gz`.
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Compression::None => "",
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+        }
+    }
+}
+
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+/// A writer that has to be explicitly finalised, e.g. to write a compression trailer.
+pub trait FinishWrite: Write + Send {
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+/// A buffered output file, optionally synced to disk once it's finished.
+pub struct FileOutput {
+    writer: BufWriter<File>,
+    fsync: bool,
+}
+
+impl Write for FileOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.writer.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl FinishWrite for FileOutput {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        let file = self
+            .writer
+            .into_inner()
+            .map_err(io::IntoInnerError::into_error)?;
+        if self.fsync {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl FinishWrite for BufWriter<Stdout> {
+    fn finish(mut self: Box<Self>) -> io::Result<()> {
+        self.flush()
+    }
+}
+
+impl<W: FinishWrite> FinishWrite for flate2::write::GzEncoder<W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Box::new((*self).finish()?).finish()
+    }
+}
+
+impl<W: FinishWrite> FinishWrite for zstd::Encoder<'static, W> {
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        Box::new((*self).finish()?).finish()
+    }
+}
+
+pub type OutputStream = Box<dyn FinishWrite>;
+
+/// Starts every record output, so readers can tell which record layout and dumper version
+/// produced it. The fields are named in every format, unlike msgpack records, which makes it
+/// stand out from them.
+#[derive(Debug, Clone, Serialize)]
+pub struct FormatHeader {
+    pub format_version: u32,
+    pub tool_version: &'static str,
+    pub demo_protocol: u32,
+}
+
+impl FormatHeader {
+    pub fn new(demo_protocol: u32) -> Self {
+        FormatHeader {
+            format_version: schema::SCHEMA_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION"),
+            demo_protocol,
+        }
+    }
+}
+
+/// Writes a stream of records in one output format. Adding a format only takes a new sink.
+pub trait RecordSink: Send {
+    /// Writes the header, before the first record. Appending to a file adds another, so readers
+    /// take every header-shaped record as one rather than only the first.
+    fn start(&mut self, header: &FormatHeader) -> Result<(), Error> {
+        self.write(header)
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error>;
+
+    /// Writes a record previously produced by `OutputFormat::encode`.
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error>;
+
+    /// Flushes and finalises the underlying output.
+    fn finish(self) -> Result<(), Error>;
+}
+
+fn finish_output(mut writer: OutputStream) -> Result<(), Error> {
+    writer.flush().map_err(Error::output)?;
+    writer.finish().map_err(Error::output)
+}
+
+/// Back to back msgpack values.
+pub struct MsgpackSink(Serializer<OutputStream>);
+
+impl MsgpackSink {
+    pub fn new(writer: OutputStream) -> Self {
+        MsgpackSink(Serializer::new(writer))
+    }
+}
+
+impl RecordSink for MsgpackSink {
+    fn start(&mut self, header: &FormatHeader) -> Result<(), Error> {
+        rmp_serde::encode::write_named(self.0.get_mut(), header).map_err(Error::output)
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        record.serialize(&mut self.0).map_err(Error::output)
+    }
+
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error> {
+        self.0.get_mut().write_all(record).map_err(Error::output)
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        finish_output(self.0.into_inner())
+    }
+}
+
+/// One JSON document per line.
+pub struct JsonlSink(OutputStream);
+
+impl JsonlSink {
+    pub fn new(writer: OutputStream) -> Self {
+        JsonlSink(writer)
+    }
+}
+
+impl RecordSink for JsonlSink {
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        serde_json::to_writer(&mut self.0, record).map_err(Error::output)?;
+        self.0.write_all(b"\n").map_err(Error::output)
+    }
+
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error> {
+        self.0.write_all(record).map_err(Error::output)
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        finish_output(self.0)
+    }
+}
+
+/// A single pretty-printed JSON document of every record, `{header, records}` with the records
+/// as an array. Without a header, e.g. when no record was written, it's just the array.
+pub struct JsonArraySink {
+    writer: OutputStream,
+    /// Whether the array has its first element yet, so the separators go in the right places.
+    started: bool,
+    /// Whether the array is inside the object holding the header, which has to be closed too.
+    wrapped: bool,
+}
+
+impl JsonArraySink {
+    pub fn new(writer: OutputStream) -> Self {
+        JsonArraySink {
+            writer,
+            started: false,
+            wrapped: false,
+        }
+    }
+
+    fn separate(&mut self) -> Result<(), Error> {
+        self.writer
+            .write_all(if self.started { b",\n" } else { b"[\n" })
+            .map_err(Error::output)?;
+        self.started = true;
+        Ok(())
+    }
+}
+
+impl RecordSink for JsonArraySink {
+    /// The header goes next to the records rather than among them, so the array only holds what
+    /// the schemas describe.
+    fn start(&mut self, header: &FormatHeader) -> Result<(), Error> {
+        self.writer
+            .write_all(b"{\n\"header\": ")
+            .map_err(Error::output)?;
+        serde_json::to_writer_pretty(&mut self.writer, header).map_err(Error::output)?;
+        self.writer
+            .write_all(b",\n\"records\": ")
+            .map_err(Error::output)?;
+        self.wrapped = true;
+        Ok(())
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        self.separate()?;
+        serde_json::to_writer_pretty(&mut self.writer, record).map_err(Error::output)
+    }
+
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error> {
+        self.separate()?;
+        self.writer.write_all(record).map_err(Error::output)
+    }
+
+    fn finish(mut self) -> Result<(), Error> {
+        self.writer
+            .write_all(if self.started { b"\n]" } else { b"[]" })
+            .map_err(Error::output)?;
+        self.writer
+            .write_all(if self.wrapped { b"\n}\n" } else { b"\n" })
+            .map_err(Error::output)?;
+        finish_output(self.writer)
+    }
+}
+
+/// The self-described CBOR tag, which marks the data that follows as CBOR to sniffing tools.
+const CBOR_SELF_DESCRIBED: u64 = 55799;
+/// How `CBOR_SELF_DESCRIBED` is encoded, which every CBOR output starts with.
+const CBOR_MAGIC: [u8; 3] = [0xd9, 0xd9, 0xf7];
+/// How the `FormatHeader` of a msgpack output starts: a map of its three fields, the first of
+/// them `format_version`, a 14 byte string.
+const MSGPACK_HEADER_START: [u8; 4] = [0x83, 0xae, b'f', b'o'];
+
+/// A CBOR sequence, each record a complete data item.
+pub struct CborSink(OutputStream);
+
+impl CborSink {
+    pub fn new(writer: OutputStream) -> Self {
+        CborSink(writer)
+    }
+}
+
+impl RecordSink for CborSink {
+    /// The header is tagged as self-described CBOR.
+    fn start(&mut self, header: &FormatHeader) -> Result<(), Error> {
+        let header = Value::serialized(header).map_err(Error::output)?;
+        let header = Value::Tag(CBOR_SELF_DESCRIBED, Box::new(header));
+        ciborium::into_writer(&header, &mut self.0).map_err(Error::output)
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        ciborium::into_writer(record, &mut self.0).map_err(Error::output)
+    }
+
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error> {
+        self.0.write_all(record).map_err(Error::output)
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        finish_output(self.0)
+    }
+}
+
+/// Comma-separated rows under a header row taken from the first record's field names.
+pub struct CsvSink(csv::Writer<OutputStream>);
+
+impl CsvSink {
+    pub fn new(writer: OutputStream) -> Self {
+        CsvSink(csv::Writer::from_writer(writer))
+    }
+}
+
+impl RecordSink for CsvSink {
+    /// A row of different columns would break the table, the header row is all it gets.
+    fn start(&mut self, _header: &FormatHeader) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        self.0.serialize(record).map_err(Error::output)
+    }
+
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error> {
+        // The CSV writer buffers on its own, so it has to be emptied before going around it
+        self.0.flush().map_err(Error::output)?;
+        self.0.get_mut().write_all(record).map_err(Error::output)
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        let writer = self
+            .0
+            .into_inner()
+            .map_err(|e| Error::output(e.into_error()))?;
+        finish_output(writer)
+    }
+}
+
+/// The side streams are JSON Lines, or a JSON document for the one-shot ones.
+pub enum StreamSink {
+    Lines(JsonlSink),
+    Document(JsonArraySink),
+}
+
+impl StreamSink {
+    pub fn new(format: OutputFormat, writer: OutputStream) -> Self {
+        match format {
+            OutputFormat::Json => StreamSink::Document(JsonArraySink::new(writer)),
+            _ => StreamSink::Lines(JsonlSink::new(writer)),
+        }
+    }
+}
+
+impl RecordSink for StreamSink {
+    fn start(&mut self, header: &FormatHeader) -> Result<(), Error> {
+        match self {
+            StreamSink::Lines(sink) => sink.start(header),
+            StreamSink::Document(sink) => sink.start(header),
+        }
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        match self {
+            StreamSink::Lines(sink) => sink.write(record),
+            StreamSink::Document(sink) => sink.write(record),
+        }
+    }
+
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error> {
+        match self {
+            StreamSink::Lines(sink) => sink.write_encoded(record),
+            StreamSink::Document(sink) => sink.write_encoded(record),
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self {
+            StreamSink::Lines(sink) => sink.finish(),
+            StreamSink::Document(sink) => sink.finish(),
+        }
+    }
+}
+
+/// When `RotatingWriter` moves on to the next chunk.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Rotation {
+    /// Bytes per chunk, counted before compression.
+    pub max_bytes: Option<u64>,
+    pub max_records: Option<u64>,
+}
+
+/// Names the chunks of a rotated output, e.g. `demo-gsd.0.msgpack`, `demo-gsd.1.msgpack`.
+pub struct ChunkPaths {
+    /// The path up to and including the dot before the chunk index.
+    prefix: OsString,
+    extension: String,
+}
+
+impl ChunkPaths {
+    pub fn new(prefix: PathBuf, extension: &str) -> Self {
+        ChunkPaths {
+            prefix: prefix.into_os_string(),
+            extension: extension.to_string(),
+        }
+    }
+
+    pub fn path(&self, index: usize) -> PathBuf {
+        let mut path = self.prefix.clone();
+        path.push(format!("{index}.{}", self.extension));
+        PathBuf::from(path)
+    }
+}
+
+/// Writes records over a series of chunk files, each a complete stream on its own, starting a
+/// new one whenever the current one reaches the `Rotation` limits.
+pub struct RotatingWriter<S> {
+    format: OutputFormat,
+    options: OutputOptions,
+    rotation: Rotation,
+    paths: ChunkPaths,
+    open_sink: fn(OutputStream) -> S,
+    /// Written at the start of every chunk.
+    header: Option<FormatHeader>,
+    current: Option<S>,
+    chunks: Vec<PathBuf>,
+    records: u64,
+    bytes: u64,
+}
+
+impl<S: RecordSink> RotatingWriter<S> {
+    /// `format` has to be the one `open_sink` writes, the records are encoded in it up front.
+    pub fn new(
+        format: OutputFormat,
+        options: OutputOptions,
+        rotation: Rotation,
+        paths: ChunkPaths,
+        open_sink: fn(OutputStream) -> S,
+    ) -> Self {
+        RotatingWriter {
+            format,
+            options,
+            rotation,
+            paths,
+            open_sink,
+            header: None,
+            current: None,
+            chunks: Vec::new(),
+            records: 0,
+            bytes: 0,
+        }
+    }
+
+    /// The chunks created so far, in order.
+    pub fn chunks(&self) -> &[PathBuf] {
+        &self.chunks
+    }
+
+    /// The sink to write a record of `size` bytes to, moving on to the next chunk if it
+    /// doesn't fit in the current one.
+    fn sink_for(&mut self, size: u64) -> Result<&mut S, Error> {
+        // A chunk never ends up without any records
+        let full = self.records > 0
+            && (self
+                .rotation
+                .max_bytes
+                .is_some_and(|max| self.bytes + size > max)
+                || self
+                    .rotation
+                    .max_records
+                    .is_some_and(|max| self.records >= max));
+        if full {
+            if let Some(chunk) = self.current.take() {
+                chunk.finish()?;
+            }
+        }
+        let sink = match &mut self.current {
+            Some(sink) => sink,
+            None => {
+                let path = self.paths.path(self.chunks.len());
+                let output =
+                    open_output(Some(&path), self.format, &self.options).map_err(|e| {
+                        Error::output(format!("couldn't create {}: {e}", path.display()))
+                    })?;
+                tracing::info!("Started output chunk {:?}.", &path);
+                self.chunks.push(path);
+                self.records = 0;
+                self.bytes = 0;
+                let sink = self.current.insert((self.open_sink)(output));
+                if let Some(header) = &self.header {
+                    sink.start(header)?;
+                }
+                sink
+            }
+        };
+        Ok(sink)
+    }
+
+    fn wrote(&mut self, size: u64) {
+        self.records += 1;
+        self.bytes += size;
+    }
+}
+
+impl<S: RecordSink> RecordSink for RotatingWriter<S> {
+    fn start(&mut self, header: &FormatHeader) -> Result<(), Error> {
+        if let Some(sink) = &mut self.current {
+            sink.start(header)?;
+        }
+        self.header = Some(header.clone());
+        Ok(())
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        // Encoded up front to know its size, and written as encoded. Except for CSV, which the
+        // sink has to write itself so each chunk gets its header row
+        let encoded = self.format.encode(record)?;
+        let size = encoded.len() as u64;
+        let csv = self.format == OutputFormat::Csv;
+        let sink = self.sink_for(size)?;
+        if csv {
+            sink.write(record)?;
+        } else {
+            sink.write_encoded(&encoded)?;
+        }
+        self.wrote(size);
+        Ok(())
+    }
+
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error> {
+        let size = record.len() as u64;
+        self.sink_for(size)?.write_encoded(record)?;
+        self.wrote(size);
+        Ok(())
+    }
+
+    /// Finishes the last chunk.
+    fn finish(self) -> Result<(), Error> {
+        match self.current {
+            Some(chunk) => chunk.finish(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The GameState delta output, a single stream or rotated over chunks.
+pub enum FrameWriter<S> {
+    Single(S),
+    Rotating(RotatingWriter<S>),
+}
+
+impl<S: RecordSink> FrameWriter<S> {
+    /// The chunks written, empty when the output isn't rotated.
+    pub fn chunks(&self) -> Vec<PathBuf> {
+        match self {
+            FrameWriter::Single(_) => Vec::new(),
+            FrameWriter::Rotating(writer) => writer.chunks().to_vec(),
+        }
+    }
+}
+
+impl<S: RecordSink> RecordSink for FrameWriter<S> {
+    fn start(&mut self, header: &FormatHeader) -> Result<(), Error> {
+        match self {
+            FrameWriter::Single(sink) => sink.start(header),
+            FrameWriter::Rotating(writer) => writer.start(header),
+        }
+    }
+
+    fn write<T: Serialize + ?Sized>(&mut self, record: &T) -> Result<(), Error> {
+        match self {
+            FrameWriter::Single(sink) => sink.write(record),
+            FrameWriter::Rotating(writer) => writer.write(record),
+        }
+    }
+
+    fn write_encoded(&mut self, record: &[u8]) -> Result<(), Error> {
+        match self {
+            FrameWriter::Single(sink) => sink.write_encoded(record),
+            FrameWriter::Rotating(writer) => writer.write_encoded(record),
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self {
+            FrameWriter::Single(sink) => sink.finish(),
+            FrameWriter::Rotating(writer) => writer.finish(),
+        }
+    }
+}
+
+/// How often opening an output file is retried when `--io-retries` isn't given.
+pub const DEFAULT_IO_RETRIES: u32 = 3;
+const FIRST_RETRY_DELAY: Duration = Duration::from_millis(250);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(8);
+
+/// How often opening an output file is retried before giving up. Every output is opened through
+/// here, so it's set once for the whole run rather than passed along to each.
+static IO_RETRIES: AtomicU32 = AtomicU32::new(DEFAULT_IO_RETRIES);
+
+pub fn set_io_retries(retries: u32) {
+    IO_RETRIES.store(retries, Ordering::Relaxed);
+}
+
+/// Runs `open` until it succeeds or the retries run out, doubling the delay between attempts.
+/// Network mounts can drop out for a moment, which shouldn't throw away a long parse.
+fn with_retries<T>(path: &Path, mut open: impl FnMut() -> io::Result<T>) -> io::Result<T> {
+    let retries = IO_RETRIES.load(Ordering::Relaxed);
+    let mut delay = FIRST_RETRY_DELAY;
+    let mut attempt = 0;
+    loop {
+        match open() {
+            Ok(value) => return Ok(value),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                tracing::warn!(
+                    "Couldn't open {:?}: {e}. Retrying in {delay:?} ({attempt}/{retries}).",
+                    path
+                );
+                thread::sleep(delay);
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+            Err(e) if retries > 0 => {
+                return Err(io::Error::new(
+                    e.kind(),
+                    format!("couldn't open {:?} after {} attempts: {e}", path, retries + 1),
+                ))
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// The demos being parsed, which no output may replace, and whether replacing an earlier output
+/// is warned about. Set once for the whole run, like the retries.
+static GUARD: OnceLock<OutputGuard> = OnceLock::new();
+
+struct OutputGuard {
+    inputs: HashSet<PathBuf>,
+    warn_existing: bool,
+}
+
+/// Protects the input demos from being written over, e.g. by a name template that ends in
+/// `.dem`. Inputs that can't be resolved, like stdin, are left out.
+pub fn guard_outputs<'a>(inputs: impl IntoIterator<Item = &'a Path>, warn_existing: bool) {
+    let inputs = inputs
+        .into_iter()
+        .filter_map(|input| fs::canonicalize(input).ok())
+        .collect();
+    let _ = GUARD.set(OutputGuard {
+        inputs,
+        warn_existing,
+    });
+}
+
+/// Refuses to write to an input demo, and warns about replacing an existing file unless it's
+/// being appended to.
+fn check_target(path: &Path, appending: bool) -> io::Result<()> {
+    let Some(guard) = GUARD.get() else {
+        return Ok(());
+    };
+    // Anything that doesn't exist yet can't be an input either
+    let Ok(target) = fs::canonicalize(path) else {
+        return Ok(());
+    };
+    if guard.inputs.contains(&target) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("refusing to write {path:?} over an input demo"),
+        ));
+    }
+    if guard.warn_existing && !appending {
+        tracing::warn!("Replacing the existing {:?}, pass --force to not be warned.", path);
+    }
+    Ok(())
+}
+
+/// Creates an output file, along with any directories leading up to it.
+fn create_file(path: &Path) -> io::Result<File> {
+    check_target(path, false)?;
+    with_retries(path, || {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        File::create(path)
+    })
+}
+
+/// Opens an output file to add to the end of, creating it if it doesn't exist yet.
+fn append_file(path: &Path, format: OutputFormat, compression: Compression) -> io::Result<File> {
+    // A mismatched target won't fix itself, so only opening it is retried
+    check_target(path, true)?;
+    check_append_target(path, format, compression)?;
+    with_retries(path, || {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::OpenOptions::new().create(true).append(true).open(path)
+    })
+}
+
+/// Refuses to append to an existing file that was written with other settings, which would
+/// leave it unreadable. Compressed streams and msgpack records concatenate cleanly otherwise.
+fn check_append_target(
+    path: &Path,
+    format: OutputFormat,
+    compression: Compression,
+) -> io::Result<()> {
+    let mut start = Vec::with_capacity(4);
+    match File::open(path) {
+        Ok(file) => file.take(4).read_to_end(&mut start)?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    if start.is_empty() {
+        return Ok(());
+    }
+    let found_compression = if start.starts_with(&GZIP_MAGIC) {
+        Compression::Gzip
+    } else if start.starts_with(&ZSTD_MAGIC) {
+        Compression::Zstd
+    } else {
+        Compression::None
+    };
+    let matches = found_compression == compression
+        && (compression != Compression::None
+            || match format {
+                // Every output starts with its header, which is a JSON object in JSON Lines
+                OutputFormat::Jsonl => start[0] == b'{',
+                OutputFormat::Msgpack => start.starts_with(&MSGPACK_HEADER_START),
+                OutputFormat::Cbor => start.starts_with(&CBOR_MAGIC),
+                OutputFormat::Json | OutputFormat::Csv => false,
+            });
+    if !matches {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "can't append to {}, it wasn't written as {format:?} with {compression:?} compression",
+                path.display()
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// Writes a single pretty-printed JSON document, for the small one-off outputs.
+pub fn write_json<T: Serialize>(path: &Path, value: &T, fsync: bool) -> io::Result<()> {
+    let mut writer = BufWriter::new(create_file(path)?);
+    serde_json::to_writer_pretty(&mut writer, value)?;
+    let file = writer.into_inner().map_err(io::IntoInnerError::into_error)?;
+    if fsync {
+        file.sync_all()?;
+    }
+    Ok(())
+}
+
+/// How output files are opened and finalised.
+#[derive(Debug, Clone, Copy)]
+pub struct OutputOptions {
+    pub compression: Compression,
+    pub level: Option<i32>,
+    /// Sync each file to disk once it's finished, not just flush it to the OS.
+    pub fsync: bool,
+    /// Add to the end of existing files instead of replacing them.
+    pub append: bool,
+    /// How much is written at once, bigger buffers make fewer writes to slow filesystems.
+    pub buffer_size: usize,
+}
+
+/// 1 MiB, well above `BufWriter`'s 8 KiB so large dumps don't make a syscall every few frames.
+pub const DEFAULT_BUFFER_SIZE: u64 = 1 << 20;
+
+/// Opens a buffered output target for records of `format`, falling back to stdout when no
+/// path is given.
+pub fn open_output(
+    path: Option<&Path>,
+    format: OutputFormat,
+    options: &OutputOptions,
+) -> io::Result<OutputStream> {
+    match path {
+        Some(path) => compress(
+            FileOutput {
+                // A JSON document can't be added to, so it's always written from scratch
+                writer: BufWriter::with_capacity(
+                    options.buffer_size,
+                    if options.append && format != OutputFormat::Json {
+                        append_file(path, format, options.compression)?
+                    } else {
+                        create_file(path)?
+                    },
+                ),
+                fsync: options.fsync,
+            },
+            options.compression,
+            options.level,
+        ),
+        None => compress(
+            BufWriter::with_capacity(options.buffer_size, io::stdout()),
+            options.compression,
+            options.level,
+        ),
+    }
+}
+
+fn compress<W: FinishWrite + 'static>(
+    writer: W,
+    compression: Compression,
+    level: Option<i32>,
+) -> io::Result<OutputStream> {
+    Ok(match compression {
+        Compression::None => Box::new(writer),
+        Compression::Gzip => {
+            let level = level.map_or(flate2::Compression::default(), |level| {
+                flate2::Compression::new(level.clamp(0, 9) as u32)
+            });
+            Box::new(flate2::write::GzEncoder::new(writer, level))
+        }
+        Compression::Zstd => Box::new(zstd::Encoder::new(
+            writer,
+            level.unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL),
+        )?),
+    })
+}
@@ -0,0 +1,199 @@
+use clap::ValueEnum;
+use indicatif::{MultiProgress, ProgressBar, ProgressState, ProgressStyle};
+use std::{
+    cell::Cell,
+    env,
+    fmt::{self, Write as _},
+    time::Duration,
+};
+
+/// How often progress is logged when the bar isn't shown.
+const LOG_INTERVAL_TICKS: u64 = 10_000;
+
+/// How many percent apart the lines of the plain style are.
+const PLAIN_STEP_PERCENT: u64 = 5;
+
+const ASCII_TICK_STRS: [&str; 5] = ["|", "/", "-", "\\", "#"];
+
+const TICK_STRS: [&str; 56] = [
+    "⢀⠀", "⡀⠀", "⠄⠀", "⢂⠀", "⡂⠀", "⠅⠀", "⢃⠀", "⡃⠀", "⠍⠀", "⢋⠀", "⡋⠀", "⠍⠁", "⢋⠁", "⡋⠁", "⠍⠉",
+    "⠋⠉", "⠋⠉", "⠉⠙", "⠉⠙", "⠉⠩", "⠈⢙", "⠈⡙", "⢈⠩", "⡀⢙", "⠄⡙", "⢂⠩", "⡂⢘", "⠅⡘", "⢃⠨", "⡃⢐",
+    "⠍⡐", "⢋⠠", "⡋⢀", "⠍⡁", "⢋⠁", "⡋⠁", "⠍⠉", "⠋⠉", "⠋⠉", "⠉⠙", "⠉⠙", "⠉⠩", "⠈⢙", "⠈⡙", "⠈⠩",
+    "⠀⢙", "⠀⡙", "⠀⠩", "⠀⢘", "⠀⡘", "⠀⠨", "⠀⢐", "⠀⡐", "⠀⠠", "⠀⢀", "⠀⡀",
+];
+
+/// What the progress bar measures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProgressMode {
+    /// Ticks parsed out of the tick count in the header.
+    Ticks,
+    /// Bytes of the demo consumed. Accurate even when the header's tick count is wrong, as it
+    /// is for cut or corrupted demos.
+    Bytes,
+}
+
+impl ProgressMode {
+    fn unit(self) -> &'static str {
+        match self {
+            ProgressMode::Ticks => "ticks",
+            ProgressMode::Bytes => "bytes",
+        }
+    }
+}
+
+/// How the progress is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum BarStyle {
+    /// A smooth bar with a braille spinner, which needs a terminal that can show Unicode.
+    Fancy,
+    /// A `[###   ]` bar, for terminals and fonts without Unicode.
+    Ascii,
+    /// No bar, just a `42%` line every few percent. Also written when stderr isn't a terminal.
+    Plain,
+}
+
+impl BarStyle {
+    /// The style for a terminal that wasn't picked one: fancy if its locale is UTF-8, else ascii.
+    pub fn detect() -> Self {
+        // The first of these that's set decides the character set, as for other programs
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .into_iter()
+            .filter_map(|name| env::var(name).ok())
+            .find(|value| !value.is_empty())
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        // Windows has no locale variables, but Windows Terminal renders Unicode fine
+        let utf8 = locale.contains("utf-8")
+            || locale.contains("utf8")
+            || (cfg!(windows) && env::var_os("WT_SESSION").is_some());
+        if utf8 {
+            BarStyle::Fancy
+        } else {
+            BarStyle::Ascii
+        }
+    }
+}
+
+/// Parse progress, either as a terminal progress bar or as periodic log lines.
+pub struct Progress {
+    bar: ProgressBar,
+    mode: ProgressMode,
+    ticks: Cell<u64>,
+    /// `None` when nothing is drawn, and progress is only logged now and then.
+    style: Option<BarStyle>,
+    /// The last percentage written by the plain style.
+    percent: Cell<u64>,
+    /// The demo being parsed, so concurrent bars and log lines can be told apart.
+    label: String,
+}
+
+impl Progress {
+    pub fn new(
+        multi: &MultiProgress,
+        mode: ProgressMode,
+        style: Option<BarStyle>,
+        label: &str,
+    ) -> Self {
+        // The plain style keeps a bar too, hidden, for its position and length
+        let bar = match style {
+            Some(BarStyle::Fancy | BarStyle::Ascii) => multi.add(ProgressBar::new(0)),
+            Some(BarStyle::Plain) | None => ProgressBar::hidden(),
+        };
+        Progress {
+            bar,
+            mode,
+            ticks: Cell::new(0),
+            style,
+            percent: Cell::new(0),
+            label: label.to_string(),
+        }
+    }
+
+    /// Starts the bar, with the header's tick count and the demo's size in bytes.
+    pub fn start(&self, total_ticks: u64, total_bytes: u64) {
+        let total = match self.mode {
+            ProgressMode::Ticks => total_ticks,
+            ProgressMode::Bytes => total_bytes,
+        };
+        self.bar.set_length(total);
+        // Throughput is measured from here, not from reading the file
+        self.bar.reset_elapsed();
+        let (template, progress_chars, tick_strs): (_, _, &[&str]) = match self.style {
+            Some(BarStyle::Fancy) => (
+                "{spinner:.green} [{elapsed_precise}] [{bar:.green}] {msg} ({eta})",
+                "█▉▊▋▌▍▎▏  ",
+                &TICK_STRS,
+            ),
+            Some(BarStyle::Ascii) => (
+                "{spinner} [{elapsed_precise}] [{bar}] {msg} ({eta})",
+                "## ",
+                &ASCII_TICK_STRS,
+            ),
+            Some(BarStyle::Plain) | None => return,
+        };
+        let bar_style_template = ProgressStyle::with_template(template)
+            .unwrap()
+            .with_key("eta", |state: &ProgressState, w: &mut dyn fmt::Write| {
+                write!(w, "{:.1}s", state.eta().as_secs_f64()).unwrap()
+            })
+            .progress_chars(progress_chars)
+            .tick_strings(tick_strs);
+        self.bar.set_style(bar_style_template);
+        self.bar.set_message(format!("Parsing {}...", self.label));
+        self.bar.enable_steady_tick(Duration::from_millis(25));
+    }
+
+    /// Advances the bar by one tick, `bytes_read` into the demo.
+    pub fn tick(&self, bytes_read: u64) {
+        let ticks = self.ticks.get() + 1;
+        self.ticks.set(ticks);
+        match self.mode {
+            ProgressMode::Ticks => self.bar.set_position(ticks),
+            ProgressMode::Bytes => self.bar.set_position(bytes_read),
+        }
+        match self.style {
+            None if ticks % LOG_INTERVAL_TICKS == 0 => {
+                tracing::info!(
+                    "{}: parsed {}/{} {}...",
+                    self.label,
+                    self.bar.position(),
+                    self.bar.length().unwrap_or_default(),
+                    self.mode.unit()
+                );
+            }
+            Some(BarStyle::Plain) => self.log_percent(),
+            _ => {}
+        }
+    }
+
+    /// Writes the percentage parsed, when it's reached the next step.
+    fn log_percent(&self) {
+        let length = self.bar.length().unwrap_or_default();
+        if length == 0 {
+            return;
+        }
+        let percent = (self.bar.position().min(length) * 100 / length) / PLAIN_STEP_PERCENT
+            * PLAIN_STEP_PERCENT;
+        if percent > self.percent.get() {
+            self.percent.set(percent);
+            tracing::info!("{}: {percent}%", self.label);
+        }
+    }
+
+    /// How many ticks have been parsed so far.
+    pub fn ticks(&self) -> u64 {
+        self.ticks.get()
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.bar.elapsed()
+    }
+
+    pub fn finish(&self) {
+        if self.style == Some(BarStyle::Plain) && self.percent.get() < 100 {
+            tracing::info!("{}: 100%", self.label);
+        }
+        self.bar
+            .finish_with_message(format!("{} parsed.", self.label));
+    }
+}
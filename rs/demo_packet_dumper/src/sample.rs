@@ -0,0 +1,269 @@
+use crate::writer::Frame;
+use serde::Serialize;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SampleMethod {
+    /// A frame from each of `frames` equal slices of the demo's ticks.
+    Strided,
+    /// A uniformly random subset of the frames.
+    Reservoir,
+}
+
+/// Which GameState frames were written with `--sample-frames`, so the sample can be reproduced.
+#[derive(Debug, Serialize)]
+pub struct SampleReport {
+    pub method: SampleMethod,
+    pub requested: usize,
+    /// The seed of the random sampling, `None` for strided sampling.
+    pub seed: Option<u64>,
+    /// Out of how many frames the sample was chosen.
+    pub offered: u64,
+    pub ticks: Vec<u32>,
+}
+
+/// Picks about `frames` GameState frames spread over the whole demo.
+///
+/// Strided sampling decides as the frames come in, so they're written straight away. Random
+/// sampling only knows which frames it keeps once the demo is done, so it holds on to them until
+/// `finish`.
+pub enum Sampler {
+    Strided {
+        frames: usize,
+        total_ticks: u32,
+        first_tick: Option<u32>,
+        last_slot: Option<u64>,
+        offered: u64,
+        ticks: Vec<u32>,
+    },
+    Reservoir {
+        frames: usize,
+        seed: u64,
+        rng: u64,
+        offered: u64,
+        kept: Vec<(u32, Vec<Frame>)>,
+    },
+}
+
+impl Sampler {
+    /// `total_ticks` is how many ticks of the demo the frames are offered from, according to its
+    /// header, which strided sampling divides up.
+    pub fn strided(frames: usize, total_ticks: u32) -> Self {
+        Sampler::Strided {
+            frames,
+            total_ticks,
+            first_tick: None,
+            last_slot: None,
+            offered: 0,
+            ticks: Vec::new(),
+        }
+    }
+
+    /// Without a `seed` one is made up from the clock.
+    pub fn reservoir(frames: usize, seed: Option<u64>) -> Self {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |time| time.as_nanos() as u64)
+        });
+        Sampler::Reservoir {
+            frames,
+            seed,
+            // Xorshift gets stuck at 0
+            rng: seed | 1,
+            offered: 0,
+            kept: Vec::with_capacity(frames),
+        }
+    }
+
+    /// Offers the frames of one tick, and hands them back if they should be written now.
+    pub fn offer(&mut self, tick: u32, tick_frames: Vec<Frame>) -> Option<Vec<Frame>> {
+        match self {
+            Sampler::Strided {
+                frames,
+                total_ticks,
+                first_tick,
+                last_slot,
+                offered,
+                ticks,
+            } => {
+                *offered += 1;
+                let first_tick = *first_tick.get_or_insert(tick);
+                let elapsed = u64::from(tick.saturating_sub(first_tick));
+                let slot = elapsed * *frames as u64 / u64::from((*total_ticks).max(1));
+                if last_slot.is_some_and(|last_slot| slot <= last_slot) {
+                    return None;
+                }
+                *last_slot = Some(slot);
+                ticks.push(tick);
+                Some(tick_frames)
+            }
+            Sampler::Reservoir {
+                frames,
+                rng,
+                offered,
+                kept,
+                ..
+            } => {
+                let index = *offered;
+                *offered += 1;
+                if kept.len() < *frames {
+                    kept.push((tick, tick_frames));
+                } else {
+                    let replace = next_random(rng) % (index + 1);
+                    if let Some(slot) = kept.get_mut(replace as usize) {
+                        *slot = (tick, tick_frames);
+                    }
+                }
+                None
+            }
+        }
+    }
+
+    /// The frames still held back, in tick order, and which ticks made it into the sample.
+    pub fn finish(self) -> (Vec<(u32, Vec<Frame>)>, SampleReport) {
+        match self {
+            Sampler::Strided {
+                frames,
+                offered,
+                ticks,
+                ..
+            } => (
+                Vec::new(),
+                SampleReport {
+                    method: SampleMethod::Strided,
+                    requested: frames,
+                    seed: None,
+                    offered,
+                    ticks,
+                },
+            ),
+            Sampler::Reservoir {
+                frames,
+                seed,
+                offered,
+                mut kept,
+                ..
+            } => {
+                kept.sort_by_key(|&(tick, _)| tick);
+                let ticks = kept.iter().map(|&(tick, _)| tick).collect();
+                (
+                    kept,
+                    SampleReport {
+                        method: SampleMethod::Reservoir,
+                        requested: frames,
+                        seed: Some(seed),
+                        offered,
+                        ticks,
+                    },
+                )
+            }
+        }
+    }
+}
+
+/// Xorshift64, plenty for picking frames and saves a dependency.
+fn next_random(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Keeps a GameState frame about every `interval` of game time, with `--dump-interval-ms`.
+pub struct IntervalSampler {
+    interval_secs: f64,
+    next_tick: Option<f64>,
+}
+
+impl IntervalSampler {
+    pub fn new(interval: Duration) -> Self {
+        IntervalSampler {
+            interval_secs: interval.as_secs_f64(),
+            next_tick: None,
+        }
+    }
+
+    /// Whether the frame at `tick` is due. Each frame is due an interval after the previous one
+    /// was, rather than after the previous one written, so frames the demo skips over don't
+    /// shift the cadence.
+    pub fn due(&mut self, tick: u32, tickrate: f64) -> bool {
+        let stride = (self.interval_secs * tickrate).max(1.0);
+        let tick = f64::from(tick);
+        let next_tick = match self.next_tick {
+            Some(next_tick) if tick < next_tick => return false,
+            Some(next_tick) => next_tick + ((tick - next_tick) / stride).floor() * stride + stride,
+            None => tick + stride,
+        };
+        self.next_tick = Some(next_tick);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Offers a frame for each of `ticks`, returning the ticks that made it into the sample.
+    fn sample(mut sampler: Sampler, ticks: impl IntoIterator<Item = u32>) -> SampleReport {
+        for tick in ticks {
+            sampler.offer(tick, Vec::new());
+        }
+        sampler.finish().1
+    }
+
+    #[test]
+    fn strided_picks_one_tick_per_slot() {
+        let report = sample(Sampler::strided(10, 1000), 0..1000);
+        assert_eq!(report.offered, 1000);
+        assert_eq!(
+            report.ticks,
+            (0..10).map(|slot| slot * 100).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn strided_slots_start_at_the_first_tick() {
+        // Every third tick of a window starting at 500, as with --tick-range
+        let report = sample(Sampler::strided(4, 400), (500..900).step_by(3));
+        assert_eq!(report.ticks, [500, 602, 701, 800]);
+    }
+
+    #[test]
+    fn strided_writes_picked_frames_straight_away() {
+        let mut sampler = Sampler::strided(2, 10);
+        assert!(sampler.offer(0, Vec::new()).is_some());
+        assert!(sampler.offer(1, Vec::new()).is_none());
+        assert!(sampler.offer(5, Vec::new()).is_some());
+        assert!(sampler.finish().0.is_empty());
+    }
+
+    #[test]
+    fn reservoir_keeps_the_requested_count_in_tick_order() {
+        let mut sampler = Sampler::reservoir(20, Some(7));
+        for tick in 0..1000 {
+            assert!(sampler.offer(tick, Vec::new()).is_none());
+        }
+        let (held, report) = sampler.finish();
+        assert_eq!(held.len(), 20);
+        assert_eq!(report.offered, 1000);
+        assert_eq!(report.seed, Some(7));
+        assert!(report.ticks.windows(2).all(|pair| pair[0] < pair[1]));
+        // Picked from the whole demo, not just its start
+        assert!(report.ticks.iter().any(|&tick| tick >= 500));
+    }
+
+    #[test]
+    fn reservoir_keeps_everything_when_offered_less() {
+        let report = sample(Sampler::reservoir(20, Some(7)), 0..5);
+        assert_eq!(report.ticks, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn reservoir_is_reproducible_from_its_seed() {
+        let picked = |seed| sample(Sampler::reservoir(20, Some(seed)), 0..1000).ticks;
+        assert_eq!(picked(42), picked(42));
+        assert_ne!(picked(42), picked(44));
+    }
+}
@@ -0,0 +1,165 @@
+//! JSON Schemas describing the records the dumper writes, so consumers can generate their types
+//! instead of reverse engineering them from sample output.
+
+use crate::streams::StreamKind;
+use schemars::{
+    schema::{RootSchema, SchemaObject},
+    schema_for, JsonSchema,
+};
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+/// The version of the record layouts described here, bumped whenever one changes in a way that
+/// breaks existing readers. Version 2 started every output with a version header.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Stand-in for the parser's vector type, which doesn't describe itself.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+pub(crate) struct Vector {
+    x: f32,
+    y: f32,
+    z: f32,
+}
+
+/// Stand-in for the parser's team, which serialises as its lowercase name.
+#[derive(JsonSchema)]
+#[serde(rename_all = "lowercase")]
+#[allow(dead_code)]
+pub(crate) enum Team {
+    Other,
+    Spectator,
+    Red,
+    Blue,
+}
+
+/// Stand-in for the parser's class, which serialises as its lowercase name.
+#[derive(JsonSchema)]
+#[serde(rename_all = "lowercase")]
+#[allow(dead_code)]
+pub(crate) enum Class {
+    Other,
+    Scout,
+    Sniper,
+    Soldier,
+    Demoman,
+    Medic,
+    Heavy,
+    Pyro,
+    Spy,
+    Engineer,
+}
+
+/// Stand-in for the parser's player state, which serialises as its variant name.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+enum PlayerState {
+    Alive,
+    Dying,
+    Death,
+    Respawnable,
+}
+
+/// Stand-in for the parser's user info, which doesn't describe itself.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct UserInfo {
+    name: String,
+    user_id: u16,
+    /// The SteamID3, e.g. `[U:1:22202]`.
+    steam_id: String,
+    entity_id: u32,
+    team: Team,
+}
+
+/// Stand-in for the parser's player, which doesn't describe itself.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct Player {
+    entity: u32,
+    position: Vector,
+    health: u16,
+    max_health: u16,
+    class: Class,
+    team: Team,
+    view_angle: f32,
+    pitch_angle: f32,
+    state: PlayerState,
+    /// `None` until the player's entry in the user info table was seen.
+    info: Option<UserInfo>,
+    /// The medic's ubercharge, in percent.
+    charge: u8,
+    simtime: u16,
+    ping: u16,
+    /// Whether the player's entity was sent this tick, rather than kept from before.
+    in_pvs: bool,
+}
+
+/// Stand-in for the parser's map bounds, which don't describe themselves.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct World {
+    boundary_min: Vector,
+    boundary_max: Vector,
+}
+
+/// Stand-in for the parser's kill, which doesn't describe itself.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct Kill {
+    attacker_id: u16,
+    assister_id: u16,
+    victim_id: u16,
+    weapon: String,
+    tick: u32,
+}
+
+/// Stand-in for the parser's GameState, which doesn't describe itself. Like the stand-ins it
+/// holds, it lists the fields in the order the parser serialises them, which the msgpack arrays
+/// follow, so it has to be kept in step with the parser version the dumper is built with.
+#[derive(JsonSchema)]
+#[allow(dead_code)]
+struct GameState {
+    players: Vec<Player>,
+    /// Keyed by entity index. Each kind of building has fields of its own, which aren't
+    /// described here.
+    buildings: BTreeMap<u32, Value>,
+    world: Option<World>,
+    kills: Vec<Kill>,
+    tick: u32,
+}
+
+/// Describes the GameState frames as JSON. The msgpack output holds the same fields, but encodes
+/// each struct as an array of its values in field order.
+const GAMESTATE_DESCRIPTION: &str = "\
+A single GameState frame. In the msgpack output every struct is an array of its values in the \
+order listed here. With --fields only the selected fields are present, and with --only-changed \
+each frame is wrapped as {tick, changed, state}.
+
+With --delta-encode the output is a keyframe followed by deltas, with another keyframe every \
+--keyframe-interval ticks. A keyframe is {tick, keyframe}, where keyframe is the whole frame with \
+every struct as a map of its fields, in msgpack as well. A delta is {tick, set, removed}, applied \
+to the frame before it: set holds [path, value] pairs to assign, and removed the paths of object \
+keys to delete. Paths are the dot separated keys and array indices leading to a value, e.g. \
+players.3.health, and an array that changed length is set whole. Reading can start at any \
+keyframe.";
+
+/// Every output's schema, keyed by the `{kind}` it's written under.
+pub fn schemas() -> BTreeMap<&'static str, RootSchema> {
+    let mut schemas = BTreeMap::new();
+    schemas.insert("gsd", gamestate_schema());
+    for kind in StreamKind::ALL {
+        schemas.insert(kind.name(), kind.schema());
+    }
+    schemas
+}
+
+/// The parser's GameState isn't `JsonSchema`, so its schema comes from the stand-in types, which
+/// describe the players an empty state wouldn't.
+fn gamestate_schema() -> RootSchema {
+    let mut schema = schema_for!(GameState);
+    let SchemaObject { metadata, .. } = &mut schema.schema;
+    metadata.get_or_insert_with(Default::default).description =
+        Some(GAMESTATE_DESCRIPTION.to_string());
+    schema
+}
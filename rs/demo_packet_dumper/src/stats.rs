@@ -0,0 +1,72 @@
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Debug,
+    mem::{self, Discriminant},
+};
+use tf_demo_parser::demo::{
+    message::{usermessage::UserMessageType, Message, MessageType},
+    packet::{Packet, PacketType},
+};
+
+/// How many of each packet, message and user message type a demo has.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PacketCounts {
+    pub packets: BTreeMap<String, u64>,
+    /// The messages carried by `Message` and `Signon` packets.
+    pub messages: BTreeMap<String, u64>,
+    /// The user messages carried by `UserMessage` messages.
+    pub user_messages: BTreeMap<String, u64>,
+}
+
+/// Counts by discriminant, so a packet costs a lookup rather than formatting its type's name,
+/// which is only done for the first of each type.
+struct Tally<T>(HashMap<Discriminant<T>, (String, u64)>);
+
+impl<T> Default for Tally<T> {
+    fn default() -> Self {
+        Tally(HashMap::new())
+    }
+}
+
+impl<T: Debug> Tally<T> {
+    fn count(&mut self, kind: T) {
+        self.0
+            .entry(mem::discriminant(&kind))
+            .or_insert_with(|| (format!("{kind:?}"), 0))
+            .1 += 1;
+    }
+
+    fn into_counts(self) -> BTreeMap<String, u64> {
+        self.0.into_values().collect()
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct PacketCounter {
+    packets: Tally<PacketType>,
+    messages: Tally<MessageType>,
+    user_messages: Tally<UserMessageType>,
+}
+
+impl PacketCounter {
+    pub(crate) fn count(&mut self, packet: &Packet<'_>) {
+        self.packets.count(packet.packet_type());
+        if let Packet::Message(packet) | Packet::Signon(packet) = packet {
+            for message in &packet.messages {
+                self.messages.count(message.get_message_type());
+                if let Message::UserMessage(user_message) = message {
+                    self.user_messages.count(user_message.get_message_type());
+                }
+            }
+        }
+    }
+
+    pub(crate) fn finish(self) -> PacketCounts {
+        PacketCounts {
+            packets: self.packets.into_counts(),
+            messages: self.messages.into_counts(),
+            user_messages: self.user_messages.into_counts(),
+        }
+    }
+}
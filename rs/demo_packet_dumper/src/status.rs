@@ -0,0 +1,92 @@
+use serde::Serialize;
+use std::{
+    fs,
+    io,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// A snapshot of parse progress, for other processes to poll.
+#[derive(Debug, Serialize)]
+pub struct Status<'a> {
+    pub demo: &'a str,
+    pub current_tick: u32,
+    pub total_ticks: u32,
+    pub percent: f64,
+    pub eta_secs: Option<f64>,
+    pub errors: usize,
+    pub done: bool,
+}
+
+impl<'a> Status<'a> {
+    /// Estimates the percentage and time left from how many ticks took how long.
+    pub fn new(
+        demo: &'a str,
+        current_tick: u32,
+        total_ticks: u32,
+        ticks_parsed: u64,
+        elapsed: Duration,
+    ) -> Self {
+        let fraction = if total_ticks > 0 {
+            (ticks_parsed as f64 / f64::from(total_ticks)).min(1.0)
+        } else {
+            0.0
+        };
+        let eta_secs =
+            (fraction > 0.0).then(|| elapsed.as_secs_f64() * (1.0 - fraction) / fraction);
+        Status {
+            demo,
+            current_tick,
+            total_ticks,
+            percent: fraction * 100.0,
+            eta_secs,
+            errors: 0,
+            done: false,
+        }
+    }
+}
+
+/// Periodically replaces a JSON status file, so a reader never sees it half written.
+pub struct StatusFile {
+    path: PathBuf,
+    temp_path: PathBuf,
+    interval: u64,
+    /// Set after the first failed write, so a broken path doesn't flood the log.
+    failed: bool,
+}
+
+impl StatusFile {
+    /// `demo` names the demo this writer reports on. With `--threads` several demos share the
+    /// file, so each writer stages its updates in a temporary file of its own, or one worker's
+    /// rename could move another's half written update into place.
+    pub fn new(path: &Path, interval: u64, demo: &str) -> Self {
+        let mut temp_path = path.as_os_str().to_owned();
+        temp_path.push(format!(".{}.{demo}.tmp", std::process::id()));
+        StatusFile {
+            path: path.to_path_buf(),
+            temp_path: temp_path.into(),
+            interval: interval.max(1),
+            failed: false,
+        }
+    }
+
+    /// Whether it's time for another update, after `ticks_parsed` ticks.
+    pub fn due(&self, ticks_parsed: u64) -> bool {
+        ticks_parsed % self.interval == 0
+    }
+
+    /// Writes the status. Failures are only logged, the status is never worth stopping a parse.
+    pub fn write(&mut self, status: &Status<'_>) {
+        if let Err(e) = self.replace(status) {
+            if !self.failed {
+                tracing::warn!("Couldn't write status file {}: {e}", self.path.display());
+            }
+            self.failed = true;
+        }
+    }
+
+    fn replace(&self, status: &Status<'_>) -> io::Result<()> {
+        fs::write(&self.temp_path, serde_json::to_vec(status)?)?;
+        fs::rename(&self.temp_path, &self.path)
+    }
+}
@@ -0,0 +1,77 @@
+use super::{recorder::RecorderView, steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::{
+    message::packetentities::EntityId,
+    packet::Packet,
+    parser::gamestateanalyser::{GameState, PlayerState},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum AngleSource {
+    /// The recorder's own client, at full precision.
+    Client,
+    /// What the server networks to everyone, rounded and only sent for the players the
+    /// recorder's client was told about.
+    Server,
+}
+
+/// Where a living player was looking, written whenever it changes.
+///
+/// In a POV demo the recorder's angles come from their client and the other players' from the
+/// server, which leaves out players the server didn't send, e.g. ones out of sight on the other
+/// side of the map. A SourceTV demo has no recorder, and is sent the angles of every player.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ViewAngles {
+    pub tick: u32,
+    pub steamid: Option<String>,
+    pub pitch: f32,
+    pub yaw: f32,
+    pub source: AngleSource,
+}
+
+#[derive(Default)]
+pub(super) struct AngleExtractor {
+    view: RecorderView,
+    /// The last angles written for each player entity, to only write changes.
+    written: HashMap<EntityId, (f32, f32)>,
+}
+
+impl Extractor for AngleExtractor {
+    fn on_packet(
+        &mut self,
+        _tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        self.view.update(packet);
+    }
+
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        let recorder = self.view.angles();
+        for player in &state.players {
+            if player.state != PlayerState::Alive {
+                continue;
+            }
+            let (pitch, yaw, source) = match recorder {
+                Some((entity, pitch, yaw)) if entity == player.entity => {
+                    (pitch, yaw, AngleSource::Client)
+                }
+                _ => (player.pitch_angle, player.view_angle, AngleSource::Server),
+            };
+            if self.written.insert(player.entity, (pitch, yaw)) == Some((pitch, yaw)) {
+                continue;
+            }
+            records.push(StreamRecord::ViewAngles(ViewAngles {
+                tick,
+                steamid: steam_id(player),
+                pitch,
+                yaw,
+                source,
+            }));
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use super::{
+    handle_target, packet_entities, player_by_entity, steam_id, Extractor, ServerClasses,
+    StreamRecord,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tf_demo_parser::demo::{
+    message::packetentities::{EntityId, UpdateType},
+    packet::Packet,
+    parser::gamestateanalyser::GameState,
+    sendprop::{SendPropIdentifier, SendPropValue},
+    vector::Vector,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildingKind {
+    Sentry,
+    Dispenser,
+    TeleporterEntrance,
+    TeleporterExit,
+}
+
+/// What changed about the building. When several things change at once, the first that applies
+/// in this order is used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum BuildingEvent {
+    /// Placed and started construction, or first seen.
+    Placed,
+    /// Destroyed, or removed by its engineer.
+    Destroyed,
+    /// Construction finished.
+    Built,
+    Upgraded,
+    Sapped,
+    SapperRemoved,
+    /// Damaged or repaired.
+    Health,
+}
+
+/// A sentry, dispenser or teleporter, written whenever its state changes.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Building {
+    pub tick: u32,
+    pub event: BuildingEvent,
+    /// Tells the records of one building apart from the others.
+    #[schemars(with = "u32")]
+    pub entity_id: EntityId,
+    pub owner_steamid: Option<String>,
+    #[serde(rename = "type")]
+    pub kind: BuildingKind,
+    pub level: u32,
+    pub health: u32,
+    #[schemars(with = "Option<crate::schema::Vector>")]
+    pub pos: Option<Vector>,
+    pub sapped: bool,
+    /// Construction has finished.
+    pub built: bool,
+}
+
+const OWNER: SendPropIdentifier = SendPropIdentifier::new("DT_BaseObject", "m_hBuilder");
+const HEALTH: SendPropIdentifier = SendPropIdentifier::new("DT_BaseObject", "m_iHealth");
+const LEVEL: SendPropIdentifier = SendPropIdentifier::new("DT_BaseObject", "m_iUpgradeLevel");
+const SAPPED: SendPropIdentifier = SendPropIdentifier::new("DT_BaseObject", "m_bHasSapper");
+const BUILDING: SendPropIdentifier = SendPropIdentifier::new("DT_BaseObject", "m_bBuilding");
+/// 0 for a teleporter entrance, 1 for an exit.
+const MODE: SendPropIdentifier = SendPropIdentifier::new("DT_BaseObject", "m_iObjectMode");
+const ORIGIN: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_vecOrigin");
+
+fn building_kind(class: &str) -> Option<BuildingKind> {
+    match class {
+        "CObjectSentrygun" => Some(BuildingKind::Sentry),
+        "CObjectDispenser" => Some(BuildingKind::Dispenser),
+        "CObjectTeleporter" => Some(BuildingKind::TeleporterEntrance),
+        _ => None,
+    }
+}
+
+struct Tracked {
+    serial: u32,
+    owner: Option<EntityId>,
+    /// The record as of the latest update, `tick` and `event` are filled in when it's written.
+    state: Building,
+    written: Option<Building>,
+}
+
+impl Tracked {
+    fn event(&self) -> Option<BuildingEvent> {
+        let Some(written) = &self.written else {
+            return Some(BuildingEvent::Placed);
+        };
+        let state = &self.state;
+        if state.built && !written.built {
+            Some(BuildingEvent::Built)
+        } else if state.level > written.level {
+            Some(BuildingEvent::Upgraded)
+        } else if state.sapped && !written.sapped {
+            Some(BuildingEvent::Sapped)
+        } else if !state.sapped && written.sapped {
+            Some(BuildingEvent::SapperRemoved)
+        } else if state.health != written.health {
+            Some(BuildingEvent::Health)
+        } else {
+            None
+        }
+    }
+}
+
+/// Follows the building entities from placement to destruction.
+#[derive(Default)]
+pub(super) struct BuildingExtractor {
+    classes: ServerClasses,
+    buildings: BTreeMap<EntityId, Tracked>,
+}
+
+impl BuildingExtractor {
+    fn record(
+        tick: u32,
+        event: BuildingEvent,
+        tracked: &Tracked,
+        state: &GameState,
+    ) -> StreamRecord {
+        StreamRecord::Building(Building {
+            tick,
+            event,
+            owner_steamid: tracked
+                .owner
+                .and_then(|owner| player_by_entity(state, owner))
+                .and_then(steam_id),
+            ..tracked.state.clone()
+        })
+    }
+
+    fn destroy(
+        &mut self,
+        tick: u32,
+        entity: EntityId,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        if let Some(tracked) = self.buildings.remove(&entity) {
+            records.push(Self::record(
+                tick,
+                BuildingEvent::Destroyed,
+                &tracked,
+                state,
+            ));
+        }
+    }
+}
+
+impl Extractor for BuildingExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        self.classes.update(packet);
+        for message in packet_entities(packet) {
+            for entity in &message.entities {
+                let id = entity.entity_index;
+                match entity.update_type {
+                    UpdateType::Delete => {
+                        self.destroy(tick, id, state, records);
+                        continue;
+                    }
+                    // The slot was reused, so the building that had it is gone
+                    UpdateType::Enter
+                        if self
+                            .buildings
+                            .get(&id)
+                            .is_some_and(|tracked| tracked.serial != entity.serial_number) =>
+                    {
+                        self.destroy(tick, id, state, records);
+                    }
+                    _ => {}
+                }
+                let Some(kind) = self
+                    .classes
+                    .name(entity.server_class)
+                    .and_then(building_kind)
+                else {
+                    continue;
+                };
+                let tracked = self.buildings.entry(id).or_insert_with(|| Tracked {
+                    serial: entity.serial_number,
+                    owner: None,
+                    state: Building {
+                        tick,
+                        event: BuildingEvent::Placed,
+                        entity_id: id,
+                        owner_steamid: None,
+                        kind,
+                        level: 1,
+                        health: 0,
+                        pos: None,
+                        sapped: false,
+                        built: false,
+                    },
+                    written: None,
+                });
+                let building = &mut tracked.state;
+                // Teleporters only find out whether they're an exit from their props
+                let teleporter = kind == BuildingKind::TeleporterEntrance;
+                for prop in &entity.props {
+                    if prop.identifier == OWNER {
+                        tracked.owner = handle_target(&prop.value);
+                    } else if prop.identifier == ORIGIN {
+                        if let SendPropValue::Vector(origin) = &prop.value {
+                            building.pos = Some(*origin);
+                        }
+                    } else if let SendPropValue::Integer(value) = prop.value {
+                        if prop.identifier == HEALTH {
+                            building.health = value.max(0) as u32;
+                        } else if prop.identifier == LEVEL {
+                            building.level = value.max(0) as u32;
+                        } else if prop.identifier == SAPPED {
+                            building.sapped = value != 0;
+                        } else if prop.identifier == BUILDING {
+                            building.built = value == 0;
+                        } else if prop.identifier == MODE && teleporter {
+                            building.kind = if value == 1 {
+                                BuildingKind::TeleporterExit
+                            } else {
+                                BuildingKind::TeleporterEntrance
+                            };
+                        }
+                    }
+                }
+            }
+            for &id in &message.removed_entities {
+                self.destroy(tick, id, state, records);
+            }
+        }
+    }
+
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        for tracked in self.buildings.values_mut() {
+            if let Some(event) = tracked.event() {
+                records.push(Self::record(tick, event, tracked, state));
+                tracked.written = Some(tracked.state.clone());
+            }
+        }
+    }
+}
@@ -0,0 +1,70 @@
+use super::{messages, name, player_by_entity, steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tf_demo_parser::demo::{
+    message::{
+        usermessage::{ChatMessageKind, UserMessage},
+        Message,
+    },
+    packet::Packet,
+    parser::gamestateanalyser::{GameState, Team},
+};
+
+/// A chat line, or a server text message when there is no sender.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ChatMessage {
+    pub tick: u32,
+    pub sender_steamid: Option<String>,
+    pub name: Option<String>,
+    #[schemars(with = "Option<crate::schema::Team>")]
+    pub team: Option<Team>,
+    pub text: String,
+    pub is_dead: bool,
+}
+
+#[derive(Default)]
+pub(super) struct ChatExtractor;
+
+impl Extractor for ChatExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        for message in messages(packet) {
+            let Message::UserMessage(user_message) = message else {
+                continue;
+            };
+            let chat = match user_message {
+                UserMessage::SayText2(say) => {
+                    let sender = player_by_entity(state, say.client);
+                    ChatMessage {
+                        tick,
+                        sender_steamid: sender.and_then(steam_id),
+                        name: sender
+                            .and_then(name)
+                            .or_else(|| say.from.as_ref().map(|from| from.to_string())),
+                        team: sender.map(|player| player.team),
+                        text: say.text.to_string(),
+                        is_dead: matches!(
+                            say.kind,
+                            ChatMessageKind::ChatAllDead | ChatMessageKind::ChatTeamDead
+                        ),
+                    }
+                }
+                UserMessage::Text(text) => ChatMessage {
+                    tick,
+                    sender_steamid: None,
+                    name: None,
+                    team: None,
+                    text: text.text.to_string(),
+                    is_dead: false,
+                },
+                _ => continue,
+            };
+            records.push(StreamRecord::Chat(chat));
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use super::{steam_id, Extractor, StreamOptions, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::{
+    message::packetentities::EntityId,
+    parser::gamestateanalyser::{Class, GameState, PlayerState},
+};
+
+/// A player switching class, or first picking one.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ClassChange {
+    pub tick: u32,
+    pub steamid: Option<String>,
+    /// `None` the first time the player is seen with a class.
+    #[schemars(with = "Option<crate::schema::Class>")]
+    pub old_class: Option<Class>,
+    #[schemars(with = "crate::schema::Class")]
+    pub new_class: Class,
+    /// The change happened as the player spawned.
+    pub respawn: bool,
+}
+
+struct Seen {
+    class: Class,
+    alive: bool,
+}
+
+pub(super) struct ClassChangeExtractor {
+    /// Also write a record for respawning as the same class.
+    include_respawns: bool,
+    players: HashMap<EntityId, Seen>,
+}
+
+impl ClassChangeExtractor {
+    pub(super) fn new(options: &StreamOptions) -> Self {
+        ClassChangeExtractor {
+            include_respawns: options.class_change_respawns,
+            players: HashMap::new(),
+        }
+    }
+}
+
+impl Extractor for ClassChangeExtractor {
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        for player in &state.players {
+            // Players that haven't picked a class yet have nothing to report
+            if player.class == Class::Other {
+                continue;
+            }
+            let alive = player.state == PlayerState::Alive;
+            let seen = self.players.insert(
+                player.entity,
+                Seen {
+                    class: player.class,
+                    alive,
+                },
+            );
+            let (old_class, respawn) = match seen {
+                Some(seen) => (Some(seen.class), alive && !seen.alive),
+                None => (None, false),
+            };
+            let changed = old_class != Some(player.class);
+            if changed || (respawn && self.include_respawns) {
+                records.push(StreamRecord::ClassChange(ClassChange {
+                    tick,
+                    steamid: steam_id(player),
+                    old_class,
+                    new_class: player.class,
+                    respawn,
+                }));
+            }
+        }
+    }
+}
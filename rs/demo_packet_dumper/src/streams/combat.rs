@@ -0,0 +1,143 @@
+use super::{game_events, name, player_by_user_id, steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent, packet::Packet, parser::gamestateanalyser::GameState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum CombatKind {
+    Damage,
+    Heal,
+}
+
+/// Damage or healing done by one player to another.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct CombatEvent {
+    pub tick: u32,
+    #[serde(rename = "type")]
+    pub kind: CombatKind,
+    /// `None` for damage done by the world, e.g. fall damage.
+    pub source: Option<String>,
+    pub target: Option<String>,
+    pub amount: u32,
+}
+
+/// Everything a player dealt and took over the whole demo.
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct PlayerCombat {
+    pub steamid: String,
+    pub name: Option<String>,
+    pub damage_dealt: u64,
+    pub damage_taken: u64,
+    pub healing_done: u64,
+    pub healing_received: u64,
+}
+
+/// The `player_hurt` and `player_healed` events of a packet, with the user ids resolved.
+fn combat_events<'s, 'a>(
+    tick: u32,
+    packet: &'s Packet<'a>,
+    state: &'s GameState,
+) -> impl Iterator<Item = CombatEvent> + use<'s, 'a> {
+    let resolve = move |user_id: u16| player_by_user_id(state, user_id).and_then(steam_id);
+    game_events(packet).filter_map(move |event| match event {
+        GameEvent::PlayerHurt(hurt) => Some(CombatEvent {
+            tick,
+            kind: CombatKind::Damage,
+            source: match hurt.attacker {
+                0 => None,
+                attacker => resolve(attacker),
+            },
+            target: resolve(hurt.user_id),
+            amount: hurt.damage_amount.into(),
+        }),
+        GameEvent::PlayerHealed(healed) => Some(CombatEvent {
+            tick,
+            kind: CombatKind::Heal,
+            source: resolve(healed.healer),
+            target: resolve(healed.patient),
+            amount: healed.amount.into(),
+        }),
+        _ => None,
+    })
+}
+
+#[derive(Default)]
+pub(super) struct CombatLogExtractor;
+
+impl Extractor for CombatLogExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        records.extend(combat_events(tick, packet, state).map(StreamRecord::Combat));
+    }
+}
+
+/// Adds up the combat log per player, written once the demo is done.
+#[derive(Default)]
+pub(super) struct CombatSummaryExtractor {
+    totals: BTreeMap<String, PlayerCombat>,
+    names: BTreeMap<String, String>,
+}
+
+impl CombatSummaryExtractor {
+    fn totals(&mut self, steamid: &str) -> &mut PlayerCombat {
+        self.totals
+            .entry(steamid.to_string())
+            .or_insert_with(|| PlayerCombat {
+                steamid: steamid.to_string(),
+                ..PlayerCombat::default()
+            })
+    }
+}
+
+impl Extractor for CombatSummaryExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        for event in combat_events(tick, packet, state) {
+            let amount = u64::from(event.amount);
+            if let Some(source) = &event.source {
+                let totals = self.totals(source);
+                match event.kind {
+                    CombatKind::Damage => totals.damage_dealt += amount,
+                    CombatKind::Heal => totals.healing_done += amount,
+                }
+            }
+            if let Some(target) = &event.target {
+                let totals = self.totals(target);
+                match event.kind {
+                    CombatKind::Damage => totals.damage_taken += amount,
+                    CombatKind::Heal => totals.healing_received += amount,
+                }
+            }
+        }
+    }
+
+    fn on_tick(&mut self, _tick: u32, state: &GameState, _records: &mut Vec<StreamRecord>) {
+        // Players may have left by the end, so their names are remembered while they're around
+        for player in &state.players {
+            if let (Some(steamid), Some(name)) = (steam_id(player), name(player)) {
+                self.names.insert(steamid, name);
+            }
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        for (steamid, mut totals) in std::mem::take(&mut self.totals) {
+            totals.name = self.names.get(&steamid).cloned();
+            records.push(StreamRecord::CombatTotals(totals));
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use super::{game_events, player_by_user_id, steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent, packet::Packet, parser::gamestateanalyser::GameState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DeathKind {
+    Kill,
+    /// The victim killed themselves.
+    Suicide,
+    /// Killed by the world, e.g. fall damage or a map hazard.
+    World,
+}
+
+/// A `player_death` event, with the user ids resolved to SteamIDs.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Death {
+    pub tick: u32,
+    pub kind: DeathKind,
+    pub attacker: Option<String>,
+    pub assister: Option<String>,
+    pub victim: Option<String>,
+    pub weapon: String,
+    pub crit: bool,
+    pub mini_crit: bool,
+    pub custom_kill: u16,
+}
+
+#[derive(Default)]
+pub(super) struct DeathExtractor;
+
+impl Extractor for DeathExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        for event in game_events(packet) {
+            let GameEvent::PlayerDeath(death) = event else {
+                continue;
+            };
+            let kind = if death.attacker == 0 {
+                DeathKind::World
+            } else if death.attacker == death.user_id {
+                DeathKind::Suicide
+            } else {
+                DeathKind::Kill
+            };
+            let resolve = |user_id: u16| player_by_user_id(state, user_id).and_then(steam_id);
+            records.push(StreamRecord::Death(Death {
+                tick,
+                kind,
+                attacker: match kind {
+                    DeathKind::World => None,
+                    _ => resolve(death.attacker),
+                },
+                // No assist is sent as -1
+                assister: match death.assister {
+                    0 | u16::MAX => None,
+                    assister => resolve(assister),
+                },
+                victim: resolve(death.user_id),
+                weapon: death.weapon.to_string(),
+                crit: death.crit_type == 2,
+                mini_crit: death.crit_type == 1,
+                custom_kill: death.custom_kill,
+            }));
+        }
+    }
+}
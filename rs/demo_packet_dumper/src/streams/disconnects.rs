@@ -0,0 +1,85 @@
+use super::{game_events, name, player_by_user_id, steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent, packet::Packet, parser::gamestateanalyser::GameState,
+};
+
+/// Why a player left, sorted from the server's disconnect message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DisconnectKind {
+    /// Quit or disconnected on purpose.
+    Left,
+    /// Stopped responding, e.g. crashed or lost connection.
+    Timeout,
+    Kicked,
+    Banned,
+    /// A reason none of the others cover, see the message.
+    Other,
+}
+
+/// A player dropping from the server.
+///
+/// A POV demo ends when its recorder disconnects, without an event for it.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Disconnect {
+    pub tick: u32,
+    pub steamid: Option<String>,
+    pub name: Option<String>,
+    pub kind: DisconnectKind,
+    /// The server's message, e.g. `Disconnect by user.`
+    pub reason: String,
+    pub bot: bool,
+}
+
+fn disconnect_kind(reason: &str) -> DisconnectKind {
+    let reason = reason.to_ascii_lowercase();
+    if reason.contains("timed out") || reason.contains("timing out") {
+        DisconnectKind::Timeout
+    } else if reason.contains("banned") {
+        DisconnectKind::Banned
+    } else if reason.contains("kicked") || reason.contains("vote") {
+        DisconnectKind::Kicked
+    } else if reason.contains("disconnect by user") || reason.contains("left the game") {
+        DisconnectKind::Left
+    } else {
+        DisconnectKind::Other
+    }
+}
+
+#[derive(Default)]
+pub(super) struct DisconnectExtractor;
+
+impl Extractor for DisconnectExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        for event in game_events(packet) {
+            let GameEvent::PlayerDisconnect(disconnect) = event else {
+                continue;
+            };
+            let reason = disconnect.reason.to_string();
+            // The player is still in the state as of the previous packet, otherwise the event
+            // carries their name and SteamID itself
+            let player = player_by_user_id(state, disconnect.user_id);
+            // Bots have `BOT` as theirs
+            let network_id = Some(disconnect.network_id.to_string())
+                .filter(|network_id| network_id.starts_with("[U:"));
+            records.push(StreamRecord::Disconnect(Disconnect {
+                tick,
+                steamid: player.and_then(steam_id).or(network_id),
+                name: player
+                    .and_then(name)
+                    .or_else(|| Some(disconnect.name.to_string())),
+                kind: disconnect_kind(&reason),
+                reason,
+                bot: disconnect.bot != 0,
+            }));
+        }
+    }
+}
@@ -0,0 +1,87 @@
+use super::{packet_entities, Extractor, ServerClasses, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::{
+    message::packetentities::{EntityId, UpdateType},
+    packet::{datatable::ClassId, Packet},
+    parser::gamestateanalyser::GameState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum EntityAction {
+    Create,
+    Update,
+    /// Deleted, or left the recorder's view.
+    Delete,
+}
+
+/// One entity update from the packet stream, for debugging the parser rather than analysis.
+/// Its shape may change between versions.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct EntityEvent {
+    pub tick: u32,
+    #[schemars(with = "u32")]
+    pub entity_id: EntityId,
+    /// The entity's server class, e.g. `CTFPlayer`. `None` if the class isn't known.
+    pub class: Option<String>,
+    pub action: EntityAction,
+}
+
+/// Writes every create, update and delete of the packet entities messages.
+#[derive(Default)]
+pub(super) struct EntityEventExtractor {
+    classes: ServerClasses,
+    /// Deletes only carry the entity index, so the class is remembered from its updates.
+    entity_classes: HashMap<EntityId, ClassId>,
+}
+
+impl EntityEventExtractor {
+    fn event(&self, tick: u32, entity_id: EntityId, action: EntityAction) -> StreamRecord {
+        let class = self
+            .entity_classes
+            .get(&entity_id)
+            .and_then(|&class| self.classes.name(class))
+            .map(str::to_string);
+        StreamRecord::EntityEvent(EntityEvent {
+            tick,
+            entity_id,
+            class,
+            action,
+        })
+    }
+}
+
+impl Extractor for EntityEventExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        self.classes.update(packet);
+        for message in packet_entities(packet) {
+            for entity in &message.entities {
+                let id = entity.entity_index;
+                let action = match entity.update_type {
+                    UpdateType::Enter => EntityAction::Create,
+                    UpdateType::Preserve => EntityAction::Update,
+                    UpdateType::Leave | UpdateType::Delete => EntityAction::Delete,
+                };
+                if action != EntityAction::Delete {
+                    self.entity_classes.insert(id, entity.server_class);
+                }
+                records.push(self.event(tick, id, action));
+                if action == EntityAction::Delete {
+                    self.entity_classes.remove(&id);
+                }
+            }
+            for &id in &message.removed_entities {
+                records.push(self.event(tick, id, EntityAction::Delete));
+                self.entity_classes.remove(&id);
+            }
+        }
+    }
+}
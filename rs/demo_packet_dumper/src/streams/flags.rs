@@ -0,0 +1,91 @@
+use super::{game_events, player_by_entity, steam_id, team_from_number, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent,
+    message::packetentities::EntityId,
+    packet::Packet,
+    parser::gamestateanalyser::{GameState, Team},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FlagAction {
+    Pickup,
+    Capture,
+    /// The flag's carrier was killed by a defender.
+    Defend,
+    Drop,
+    /// Returned to its base, by a defender touching it or by its reset timer running out.
+    Return,
+}
+
+/// Something happening to a CTF flag or A/D intelligence.
+///
+/// Flags are only told apart by their team, maps with more than one flag per team don't say
+/// which of them it was.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct FlagEvent {
+    pub tick: u32,
+    /// The player that did it, `None` for a flag resetting itself.
+    pub steamid: Option<String>,
+    pub action: FlagAction,
+    /// The team the flag belongs to, `None` for a neutral flag.
+    #[schemars(with = "Option<crate::schema::Team>")]
+    pub flag_team: Option<Team>,
+    /// The carrier that was killed, for a defend.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub carrier_steamid: Option<String>,
+}
+
+fn flag_action(event_type: u16) -> Option<FlagAction> {
+    match event_type {
+        1 => Some(FlagAction::Pickup),
+        2 => Some(FlagAction::Capture),
+        3 => Some(FlagAction::Defend),
+        4 => Some(FlagAction::Drop),
+        5 => Some(FlagAction::Return),
+        _ => None,
+    }
+}
+
+/// The player with this entity index, 0 being nobody.
+fn player_steam_id(state: &GameState, index: u16) -> Option<String> {
+    if index == 0 {
+        return None;
+    }
+    player_by_entity(state, EntityId::from(u32::from(index))).and_then(steam_id)
+}
+
+#[derive(Default)]
+pub(super) struct FlagEventExtractor;
+
+impl Extractor for FlagEventExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        for event in game_events(packet) {
+            let GameEvent::TeamPlayFlagEvent(flag) = event else {
+                continue;
+            };
+            let Some(action) = flag_action(flag.event_type) else {
+                continue;
+            };
+            let carrier_steamid = match action {
+                FlagAction::Defend => player_steam_id(state, flag.carrier),
+                _ => None,
+            };
+            records.push(StreamRecord::FlagEvent(FlagEvent {
+                tick,
+                steamid: player_steam_id(state, flag.player),
+                action,
+                flag_team: team_from_number(flag.team),
+                carrier_steamid,
+            }));
+        }
+    }
+}
@@ -0,0 +1,142 @@
+use super::{
+    handle_target, packet_entities, player_by_entity, steam_id, Extractor, ServerClasses,
+    StreamRecord,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use tf_demo_parser::demo::{
+    message::packetentities::{EntityId, UpdateType},
+    packet::{datatable::ClassId, Packet},
+    parser::gamestateanalyser::GameState,
+    sendprop::{SendPropIdentifier, SendPropValue},
+};
+
+/// An econ item, a weapon or a cosmetic.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, JsonSchema)]
+pub struct Item {
+    pub item_definition_index: u32,
+    /// The item's server class, e.g. `CTFWearable` for most cosmetics.
+    pub class: String,
+    /// The econ quality, e.g. 5 for unusual or 11 for strange.
+    pub quality: Option<u32>,
+}
+
+/// Every item a player had equipped at some point in the demo.
+///
+/// Paints and war paints are item attributes, which aren't included.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct PlayerItems {
+    pub steamid: String,
+    pub items: Vec<Item>,
+}
+
+const OWNER: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_hOwnerEntity");
+const ITEM_DEFINITION_INDEX: SendPropIdentifier =
+    SendPropIdentifier::new("DT_ScriptCreatedItem", "m_iItemDefinitionIndex");
+const QUALITY: SendPropIdentifier =
+    SendPropIdentifier::new("DT_ScriptCreatedItem", "m_iEntityQuality");
+
+#[derive(Default)]
+struct ItemEntity {
+    class: Option<ClassId>,
+    item_definition_index: Option<u32>,
+    quality: Option<u32>,
+    owner: Option<EntityId>,
+}
+
+/// Follows the item entities and who owns them, collecting each player's items once they're
+/// known.
+#[derive(Default)]
+pub(super) struct ItemExtractor {
+    classes: ServerClasses,
+    entities: HashMap<EntityId, ItemEntity>,
+    /// An item entity was updated since the last tick, or an item's owning player wasn't known
+    /// yet.
+    changed: bool,
+    players: BTreeMap<String, BTreeSet<Item>>,
+}
+
+impl Extractor for ItemExtractor {
+    fn on_packet(
+        &mut self,
+        _tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        self.classes.update(packet);
+        for message in packet_entities(packet) {
+            for entity in &message.entities {
+                let id = entity.entity_index;
+                if entity.update_type == UpdateType::Delete {
+                    self.entities.remove(&id);
+                    continue;
+                }
+                for prop in &entity.props {
+                    if ![OWNER, ITEM_DEFINITION_INDEX, QUALITY].contains(&prop.identifier) {
+                        continue;
+                    }
+                    let item = self.entities.entry(id).or_default();
+                    item.class = Some(entity.server_class);
+                    self.changed = true;
+                    if prop.identifier == OWNER {
+                        item.owner = handle_target(&prop.value);
+                    } else if let SendPropValue::Integer(value) = prop.value {
+                        if prop.identifier == ITEM_DEFINITION_INDEX {
+                            item.item_definition_index = Some(value as u32);
+                        } else {
+                            item.quality = Some(value as u32);
+                        }
+                    }
+                }
+            }
+            for id in &message.removed_entities {
+                self.entities.remove(id);
+            }
+        }
+    }
+
+    fn on_tick(&mut self, _tick: u32, state: &GameState, _records: &mut Vec<StreamRecord>) {
+        if !std::mem::take(&mut self.changed) {
+            return;
+        }
+        for entity in self.entities.values() {
+            let (Some(class), Some(item_definition_index), Some(owner)) =
+                (entity.class, entity.item_definition_index, entity.owner)
+            else {
+                continue;
+            };
+            let Some(player) = player_by_entity(state, owner) else {
+                // Owned by something other than a player, e.g. the world, which never resolves
+                continue;
+            };
+            let Some(steamid) = steam_id(player) else {
+                // The player's info can lag behind their entity
+                self.changed = true;
+                continue;
+            };
+            let Some(class) = self.classes.name(class) else {
+                continue;
+            };
+            self.players.entry(steamid).or_default().insert(Item {
+                item_definition_index,
+                class: class.to_string(),
+                quality: entity.quality,
+            });
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        records.extend(
+            std::mem::take(&mut self.players)
+                .into_iter()
+                .map(|(steamid, items)| {
+                    StreamRecord::PlayerItems(PlayerItems {
+                        steamid,
+                        items: items.into_iter().collect(),
+                    })
+                }),
+        );
+    }
+}
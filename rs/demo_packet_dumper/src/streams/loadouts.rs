@@ -0,0 +1,151 @@
+use super::{handle_target, packet_entities, steam_id, Extractor, ServerClasses, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use tf_demo_parser::demo::{
+    message::packetentities::{EntityId, UpdateType},
+    packet::{datatable::ClassId, Packet},
+    parser::gamestateanalyser::GameState,
+    sendprop::{SendPropIdentifier, SendPropValue},
+};
+
+/// A weapon a player is carrying.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, JsonSchema)]
+pub struct Weapon {
+    /// The weapon's server class, e.g. `CTFRocketLauncher`.
+    pub class: String,
+    /// The econ item definition, telling apart weapons that share a class.
+    pub item_definition_index: Option<u32>,
+}
+
+/// A player's weapons, written whenever they switch weapons or their loadout changes.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Loadout {
+    pub tick: u32,
+    pub steamid: Option<String>,
+    pub active_weapon: Option<Weapon>,
+    pub weapon_slots: Vec<Weapon>,
+}
+
+const ACTIVE_WEAPON: SendPropIdentifier =
+    SendPropIdentifier::new("DT_BaseCombatCharacter", "m_hActiveWeapon");
+const ITEM_DEFINITION_INDEX: SendPropIdentifier =
+    SendPropIdentifier::new("DT_ScriptCreatedItem", "m_iItemDefinitionIndex");
+/// The number of elements of the `m_hMyWeapons` array.
+const WEAPON_SLOTS: usize = 48;
+
+#[derive(Default)]
+struct Carried {
+    active: Option<EntityId>,
+    slots: BTreeMap<usize, EntityId>,
+}
+
+/// Follows the weapon handles of the player entities.
+///
+/// Only the props sent in each update are seen, not the class baselines, which never
+/// hold a weapon handle.
+pub(super) struct LoadoutExtractor {
+    slot_props: Vec<SendPropIdentifier>,
+    classes: ServerClasses,
+    entity_classes: HashMap<EntityId, ClassId>,
+    item_definitions: HashMap<EntityId, u32>,
+    carried: HashMap<EntityId, Carried>,
+    /// The last loadout written for each player entity, to only write changes.
+    written: HashMap<EntityId, (Option<Weapon>, Vec<Weapon>)>,
+}
+
+impl LoadoutExtractor {
+    pub(super) fn new() -> Self {
+        LoadoutExtractor {
+            slot_props: (0..WEAPON_SLOTS)
+                .map(|slot| SendPropIdentifier::new("m_hMyWeapons", &format!("{slot:03}")))
+                .collect(),
+            classes: ServerClasses::default(),
+            entity_classes: HashMap::new(),
+            item_definitions: HashMap::new(),
+            carried: HashMap::new(),
+            written: HashMap::new(),
+        }
+    }
+
+    fn weapon(&self, entity: EntityId) -> Option<Weapon> {
+        let class = self.entity_classes.get(&entity)?;
+        Some(Weapon {
+            class: self.classes.name(*class)?.to_string(),
+            item_definition_index: self.item_definitions.get(&entity).copied(),
+        })
+    }
+
+    fn remove(&mut self, entity: EntityId) {
+        self.entity_classes.remove(&entity);
+        self.item_definitions.remove(&entity);
+        self.carried.remove(&entity);
+    }
+}
+
+impl Extractor for LoadoutExtractor {
+    fn on_packet(
+        &mut self,
+        _tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        self.classes.update(packet);
+        for message in packet_entities(packet) {
+            for entity in &message.entities {
+                let id = entity.entity_index;
+                if entity.update_type == UpdateType::Delete {
+                    self.remove(id);
+                    continue;
+                }
+                self.entity_classes.insert(id, entity.server_class);
+                for prop in &entity.props {
+                    if prop.identifier == ITEM_DEFINITION_INDEX {
+                        if let SendPropValue::Integer(index) = prop.value {
+                            self.item_definitions.insert(id, index as u32);
+                        }
+                    } else if prop.identifier == ACTIVE_WEAPON {
+                        self.carried.entry(id).or_default().active = handle_target(&prop.value);
+                    } else if let Some(slot) =
+                        self.slot_props.iter().position(|slot| *slot == prop.identifier)
+                    {
+                        let slots = &mut self.carried.entry(id).or_default().slots;
+                        match handle_target(&prop.value) {
+                            Some(weapon) => slots.insert(slot, weapon),
+                            None => slots.remove(&slot),
+                        };
+                    }
+                }
+            }
+            for &id in &message.removed_entities {
+                self.remove(id);
+            }
+        }
+    }
+
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        for player in &state.players {
+            let Some(carried) = self.carried.get(&player.entity) else {
+                continue;
+            };
+            let active_weapon = carried.active.and_then(|weapon| self.weapon(weapon));
+            let weapon_slots: Vec<Weapon> = carried
+                .slots
+                .values()
+                .filter_map(|&weapon| self.weapon(weapon))
+                .collect();
+            let loadout = (active_weapon, weapon_slots);
+            if self.written.get(&player.entity) == Some(&loadout) {
+                continue;
+            }
+            records.push(StreamRecord::Loadout(Loadout {
+                tick,
+                steamid: steam_id(player),
+                active_weapon: loadout.0.clone(),
+                weapon_slots: loadout.1.clone(),
+            }));
+            self.written.insert(player.entity, loadout);
+        }
+    }
+}
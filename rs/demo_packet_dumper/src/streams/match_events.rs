@@ -0,0 +1,82 @@
+use super::{game_events, messages, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent, message::Message, packet::Packet,
+    parser::gamestateanalyser::GameState,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchEventKind {
+    /// The server was paused, the ticks in between aren't game time.
+    Pause,
+    /// The round was restarted without a result, e.g. by `mp_tournament_restart`.
+    Restart,
+}
+
+/// Something that interrupts the match clock, which time calculations from ticks have to
+/// leave out.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct MatchEvent {
+    #[serde(rename = "type")]
+    pub kind: MatchEventKind,
+    pub start_tick: u32,
+    /// The tick the pause was lifted, the same as `start_tick` for a restart. `None` if the demo
+    /// ended while paused.
+    pub end_tick: Option<u32>,
+}
+
+#[derive(Default)]
+pub(super) struct MatchEventExtractor {
+    /// The tick the current pause started at.
+    paused_at: Option<u32>,
+}
+
+impl Extractor for MatchEventExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        for message in messages(packet) {
+            let Message::SetPause(pause) = message else {
+                continue;
+            };
+            match (pause.pause, self.paused_at) {
+                (true, None) => self.paused_at = Some(tick),
+                (false, Some(start_tick)) => {
+                    self.paused_at = None;
+                    records.push(StreamRecord::MatchEvent(MatchEvent {
+                        kind: MatchEventKind::Pause,
+                        start_tick,
+                        end_tick: Some(tick),
+                    }));
+                }
+                // Repeated, nothing changed
+                _ => {}
+            }
+        }
+        for event in game_events(packet) {
+            if let GameEvent::TeamPlayRestartRound(_) = event {
+                records.push(StreamRecord::MatchEvent(MatchEvent {
+                    kind: MatchEventKind::Restart,
+                    start_tick: tick,
+                    end_tick: Some(tick),
+                }));
+            }
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        if let Some(start_tick) = self.paused_at.take() {
+            records.push(StreamRecord::MatchEvent(MatchEvent {
+                kind: MatchEventKind::Pause,
+                start_tick,
+                end_tick: None,
+            }));
+        }
+    }
+}
@@ -0,0 +1,475 @@
+//! Secondary record streams derived from the packets and game state seen during a parse.
+
+mod angles;
+mod buildings;
+mod chat;
+mod classes;
+mod combat;
+mod deaths;
+mod disconnects;
+mod entities;
+mod flags;
+mod items;
+mod loadouts;
+mod match_events;
+mod player_scores;
+mod players;
+mod positions;
+mod projectiles;
+mod recorder;
+mod respawns;
+mod rounds;
+mod scoreboard;
+mod server_info;
+mod string_tables;
+mod teams;
+mod ubercharge;
+mod voice;
+
+pub use angles::{AngleSource, ViewAngles};
+pub use buildings::{Building, BuildingEvent, BuildingKind};
+pub use chat::ChatMessage;
+pub use classes::ClassChange;
+pub use combat::{CombatEvent, CombatKind, PlayerCombat};
+pub use deaths::{Death, DeathKind};
+pub use disconnects::{Disconnect, DisconnectKind};
+pub use entities::{EntityAction, EntityEvent};
+pub use flags::{FlagAction, FlagEvent};
+pub use items::{Item, PlayerItems};
+pub use loadouts::{Loadout, Weapon};
+pub use match_events::{MatchEvent, MatchEventKind};
+pub use player_scores::PlayerScore;
+pub use players::{ClassTime, PlayerSummary};
+pub use positions::Position;
+pub use projectiles::Projectile;
+pub use recorder::Recorder;
+pub use respawns::RespawnTiming;
+pub use rounds::Round;
+pub use scoreboard::{ScoreEvent, ScoreUpdate};
+pub use server_info::{ConVarChange, ServerSettings};
+pub use string_tables::{StringTable, StringTableItem};
+pub use teams::TeamChange;
+pub use ubercharge::{UberEvent, UberType, Ubercharge};
+pub use voice::VoiceActivity;
+
+use schemars::{schema::RootSchema, schema_for};
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent,
+    message::{packetentities::PacketEntitiesMessage, Message},
+    packet::{datatable::ClassId, Packet},
+    parser::gamestateanalyser::{GameState, Player, Team},
+    sendprop::SendPropValue,
+};
+use tf_demo_parser::demo::message::packetentities::EntityId;
+
+/// The side streams that can be extracted alongside the GameState deltas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum StreamKind {
+    Chat,
+    Deaths,
+    Positions,
+    Rounds,
+    Loadouts,
+    VoiceActivity,
+    Scoreboard,
+    CombatLog,
+    CombatSummary,
+    Projectiles,
+    ClassChanges,
+    Ubercharge,
+    EntityEvents,
+    PlayerScores,
+    Buildings,
+    Players,
+    Recorder,
+    TeamChanges,
+    MatchEvents,
+    Disconnects,
+    Items,
+    Angles,
+    StringTables,
+    RespawnTiming,
+    FlagEvents,
+    ServerInfo,
+}
+
+impl StreamKind {
+    /// Every stream, in the order they're declared.
+    pub const ALL: &'static [StreamKind] = &[
+        StreamKind::Chat,
+        StreamKind::Deaths,
+        StreamKind::Positions,
+        StreamKind::Rounds,
+        StreamKind::Loadouts,
+        StreamKind::VoiceActivity,
+        StreamKind::Scoreboard,
+        StreamKind::CombatLog,
+        StreamKind::CombatSummary,
+        StreamKind::Projectiles,
+        StreamKind::ClassChanges,
+        StreamKind::Ubercharge,
+        StreamKind::EntityEvents,
+        StreamKind::PlayerScores,
+        StreamKind::Buildings,
+        StreamKind::Players,
+        StreamKind::Recorder,
+        StreamKind::TeamChanges,
+        StreamKind::MatchEvents,
+        StreamKind::Disconnects,
+        StreamKind::Items,
+        StreamKind::Angles,
+        StreamKind::StringTables,
+        StreamKind::RespawnTiming,
+        StreamKind::FlagEvents,
+        StreamKind::ServerInfo,
+    ];
+
+    /// Name used for the stream's output file, e.g. `chat` in `{demo_name}-chat.jsonl`.
+    pub fn name(self) -> &'static str {
+        match self {
+            StreamKind::Chat => "chat",
+            StreamKind::Deaths => "deaths",
+            StreamKind::Positions => "positions",
+            StreamKind::Rounds => "rounds",
+            StreamKind::Loadouts => "loadouts",
+            StreamKind::VoiceActivity => "voice-activity",
+            StreamKind::Scoreboard => "scoreboard",
+            StreamKind::CombatLog => "combat-log",
+            StreamKind::CombatSummary => "combat-summary",
+            StreamKind::Projectiles => "projectiles",
+            StreamKind::ClassChanges => "class-changes",
+            StreamKind::Ubercharge => "ubercharge",
+            StreamKind::EntityEvents => "entity-events",
+            StreamKind::PlayerScores => "player-scores",
+            StreamKind::Buildings => "buildings",
+            StreamKind::Players => "players",
+            StreamKind::Recorder => "recorder",
+            StreamKind::TeamChanges => "team-changes",
+            StreamKind::MatchEvents => "events",
+            StreamKind::Disconnects => "disconnects",
+            StreamKind::Items => "items",
+            StreamKind::Angles => "angles",
+            StreamKind::StringTables => "stringtables",
+            StreamKind::RespawnTiming => "respawn-timing",
+            StreamKind::FlagEvents => "flag-events",
+            StreamKind::ServerInfo => "serverinfo",
+        }
+    }
+
+    /// Whether the stream is written as a single JSON document rather than one record per line.
+    pub fn is_document(self) -> bool {
+        matches!(
+            self,
+            StreamKind::Rounds
+                | StreamKind::CombatSummary
+                | StreamKind::Players
+                | StreamKind::MatchEvents
+                | StreamKind::Items
+                | StreamKind::StringTables
+                | StreamKind::ServerInfo
+        )
+    }
+
+    /// A JSON Schema describing the stream's records.
+    pub fn schema(self) -> RootSchema {
+        match self {
+            StreamKind::Chat => schema_for!(ChatMessage),
+            StreamKind::Deaths => schema_for!(Death),
+            StreamKind::Positions => schema_for!(Position),
+            StreamKind::Rounds => schema_for!(Round),
+            StreamKind::Loadouts => schema_for!(Loadout),
+            StreamKind::VoiceActivity => schema_for!(VoiceActivity),
+            StreamKind::Scoreboard => schema_for!(ScoreUpdate),
+            StreamKind::CombatLog => schema_for!(CombatEvent),
+            StreamKind::CombatSummary => schema_for!(PlayerCombat),
+            StreamKind::Projectiles => schema_for!(Projectile),
+            StreamKind::ClassChanges => schema_for!(ClassChange),
+            StreamKind::Ubercharge => schema_for!(Ubercharge),
+            StreamKind::EntityEvents => schema_for!(EntityEvent),
+            StreamKind::PlayerScores => schema_for!(PlayerScore),
+            StreamKind::Buildings => schema_for!(Building),
+            StreamKind::Players => schema_for!(PlayerSummary),
+            StreamKind::Recorder => schema_for!(Recorder),
+            StreamKind::TeamChanges => schema_for!(TeamChange),
+            StreamKind::MatchEvents => schema_for!(MatchEvent),
+            StreamKind::Disconnects => schema_for!(Disconnect),
+            StreamKind::Items => schema_for!(PlayerItems),
+            StreamKind::Angles => schema_for!(ViewAngles),
+            StreamKind::StringTables => schema_for!(StringTable),
+            StreamKind::RespawnTiming => schema_for!(RespawnTiming),
+            StreamKind::FlagEvents => schema_for!(FlagEvent),
+            StreamKind::ServerInfo => schema_for!(ServerSettings),
+        }
+    }
+
+    fn extractor(self, options: &StreamOptions) -> Box<dyn Extractor> {
+        match self {
+            StreamKind::Chat => Box::<chat::ChatExtractor>::default(),
+            StreamKind::Deaths => Box::<deaths::DeathExtractor>::default(),
+            StreamKind::Positions => Box::new(positions::PositionExtractor::new(options)),
+            StreamKind::Rounds => Box::<rounds::RoundExtractor>::default(),
+            StreamKind::Loadouts => Box::new(loadouts::LoadoutExtractor::new()),
+            StreamKind::VoiceActivity => Box::<voice::VoiceExtractor>::default(),
+            StreamKind::Scoreboard => Box::<scoreboard::ScoreboardExtractor>::default(),
+            StreamKind::CombatLog => Box::<combat::CombatLogExtractor>::default(),
+            StreamKind::CombatSummary => Box::<combat::CombatSummaryExtractor>::default(),
+            StreamKind::Projectiles => Box::<projectiles::ProjectileExtractor>::default(),
+            StreamKind::ClassChanges => Box::new(classes::ClassChangeExtractor::new(options)),
+            StreamKind::Ubercharge => Box::<ubercharge::UberchargeExtractor>::default(),
+            StreamKind::EntityEvents => Box::<entities::EntityEventExtractor>::default(),
+            StreamKind::PlayerScores => Box::new(player_scores::PlayerScoreExtractor::new()),
+            StreamKind::Buildings => Box::<buildings::BuildingExtractor>::default(),
+            StreamKind::Players => Box::new(players::PlayerSummaryExtractor::new()),
+            StreamKind::Recorder => Box::<recorder::RecorderExtractor>::default(),
+            StreamKind::TeamChanges => Box::<teams::TeamChangeExtractor>::default(),
+            StreamKind::MatchEvents => Box::<match_events::MatchEventExtractor>::default(),
+            StreamKind::Disconnects => Box::<disconnects::DisconnectExtractor>::default(),
+            StreamKind::Items => Box::<items::ItemExtractor>::default(),
+            StreamKind::Angles => Box::<angles::AngleExtractor>::default(),
+            StreamKind::StringTables => Box::<string_tables::StringTableExtractor>::default(),
+            StreamKind::RespawnTiming => Box::<respawns::RespawnExtractor>::default(),
+            StreamKind::FlagEvents => Box::<flags::FlagEventExtractor>::default(),
+            StreamKind::ServerInfo => Box::<server_info::ServerInfoExtractor>::default(),
+        }
+    }
+}
+
+/// Settings for the side streams that take any.
+#[derive(Debug, Clone)]
+pub struct StreamOptions {
+    /// Sample player positions every this many ticks.
+    pub position_stride: u32,
+    /// Fill in the positions between two updates of a player, instead of repeating the stale one.
+    pub interpolate_positions: bool,
+    /// Also record players respawning as the class they already were.
+    pub class_change_respawns: bool,
+}
+
+impl Default for StreamOptions {
+    fn default() -> Self {
+        StreamOptions {
+            position_stride: 1,
+            interpolate_positions: false,
+            class_change_respawns: false,
+        }
+    }
+}
+
+/// A single record of one of the side streams.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum StreamRecord {
+    Chat(ChatMessage),
+    Death(Death),
+    Position(Position),
+    Round(Round),
+    Loadout(Loadout),
+    Voice(VoiceActivity),
+    Score(ScoreUpdate),
+    Combat(CombatEvent),
+    CombatTotals(PlayerCombat),
+    Projectile(Projectile),
+    ClassChange(ClassChange),
+    Ubercharge(Ubercharge),
+    EntityEvent(EntityEvent),
+    PlayerScore(PlayerScore),
+    Building(Building),
+    PlayerSummary(PlayerSummary),
+    Recorder(Recorder),
+    TeamChange(TeamChange),
+    MatchEvent(MatchEvent),
+    Disconnect(Disconnect),
+    PlayerItems(PlayerItems),
+    ViewAngles(ViewAngles),
+    StringTable(StringTable),
+    RespawnTiming(RespawnTiming),
+    FlagEvent(FlagEvent),
+    ServerSettings(ServerSettings),
+}
+
+impl StreamRecord {
+    pub fn kind(&self) -> StreamKind {
+        match self {
+            StreamRecord::Chat(_) => StreamKind::Chat,
+            StreamRecord::Death(_) => StreamKind::Deaths,
+            StreamRecord::Position(_) => StreamKind::Positions,
+            StreamRecord::Round(_) => StreamKind::Rounds,
+            StreamRecord::Loadout(_) => StreamKind::Loadouts,
+            StreamRecord::Voice(_) => StreamKind::VoiceActivity,
+            StreamRecord::Score(_) => StreamKind::Scoreboard,
+            StreamRecord::Combat(_) => StreamKind::CombatLog,
+            StreamRecord::CombatTotals(_) => StreamKind::CombatSummary,
+            StreamRecord::Projectile(_) => StreamKind::Projectiles,
+            StreamRecord::ClassChange(_) => StreamKind::ClassChanges,
+            StreamRecord::Ubercharge(_) => StreamKind::Ubercharge,
+            StreamRecord::EntityEvent(_) => StreamKind::EntityEvents,
+            StreamRecord::PlayerScore(_) => StreamKind::PlayerScores,
+            StreamRecord::Building(_) => StreamKind::Buildings,
+            StreamRecord::PlayerSummary(_) => StreamKind::Players,
+            StreamRecord::Recorder(_) => StreamKind::Recorder,
+            StreamRecord::TeamChange(_) => StreamKind::TeamChanges,
+            StreamRecord::MatchEvent(_) => StreamKind::MatchEvents,
+            StreamRecord::Disconnect(_) => StreamKind::Disconnects,
+            StreamRecord::PlayerItems(_) => StreamKind::Items,
+            StreamRecord::ViewAngles(_) => StreamKind::Angles,
+            StreamRecord::StringTable(_) => StreamKind::StringTables,
+            StreamRecord::RespawnTiming(_) => StreamKind::RespawnTiming,
+            StreamRecord::FlagEvent(_) => StreamKind::FlagEvents,
+            StreamRecord::ServerSettings(_) => StreamKind::ServerInfo,
+        }
+    }
+}
+
+/// Produces the records of one side stream.
+///
+/// Every hook has a no-op default, so extractors only implement what they need.
+trait Extractor {
+    /// Called with every packet before it's handled, with the state as of the previous packet.
+    fn on_packet(
+        &mut self,
+        _tick: u32,
+        _packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+    }
+
+    /// Called once each time the server tick advances, after the state was updated.
+    fn on_tick(&mut self, _tick: u32, _state: &GameState, _records: &mut Vec<StreamRecord>) {}
+
+    /// Called once after the last packet.
+    fn finish(&mut self, _tick: u32, _state: &GameState, _records: &mut Vec<StreamRecord>) {}
+}
+
+/// Drives the extractors for every requested stream.
+pub(crate) struct Extractors {
+    extractors: Vec<Box<dyn Extractor>>,
+    records: Vec<StreamRecord>,
+}
+
+impl Extractors {
+    pub(crate) fn new(kinds: &[StreamKind], options: &StreamOptions) -> Self {
+        Extractors {
+            extractors: kinds.iter().map(|kind| kind.extractor(options)).collect(),
+            records: Vec::new(),
+        }
+    }
+
+    pub(crate) fn on_packet(&mut self, tick: u32, packet: &Packet<'_>, state: &GameState) {
+        for extractor in &mut self.extractors {
+            extractor.on_packet(tick, packet, state, &mut self.records);
+        }
+    }
+
+    pub(crate) fn on_tick(&mut self, tick: u32, state: &GameState) {
+        for extractor in &mut self.extractors {
+            extractor.on_tick(tick, state, &mut self.records);
+        }
+    }
+
+    pub(crate) fn finish(&mut self, tick: u32, state: &GameState) {
+        for extractor in &mut self.extractors {
+            extractor.finish(tick, state, &mut self.records);
+        }
+    }
+
+    /// Takes the records produced since the last call.
+    pub(crate) fn drain(&mut self) -> std::vec::Drain<'_, StreamRecord> {
+        self.records.drain(..)
+    }
+}
+
+/// The messages carried by a packet, if it carries any.
+fn messages<'p, 'a>(packet: &'p Packet<'a>) -> &'p [Message<'a>] {
+    match packet {
+        Packet::Message(packet) | Packet::Signon(packet) => &packet.messages,
+        _ => &[],
+    }
+}
+
+/// The game events carried by a packet.
+fn game_events<'p, 'a>(
+    packet: &'p Packet<'a>,
+) -> impl Iterator<Item = &'p GameEvent> + use<'p, 'a> {
+    messages(packet).iter().filter_map(|message| match message {
+        Message::GameEvent(message) => Some(&message.event),
+        _ => None,
+    })
+}
+
+/// The entity updates carried by a packet.
+fn packet_entities<'p, 'a>(
+    packet: &'p Packet<'a>,
+) -> impl Iterator<Item = &'p PacketEntitiesMessage> + use<'p, 'a> {
+    messages(packet).iter().filter_map(|message| match message {
+        Message::PacketEntities(message) => Some(message),
+        _ => None,
+    })
+}
+
+/// Entity handles carry a serial number above the entity index.
+const HANDLE_INDEX_MASK: i64 = (1 << 11) - 1;
+const INVALID_HANDLE: i64 = (1 << 21) - 1;
+
+/// The entity an entity handle prop points at.
+fn handle_target(value: &SendPropValue) -> Option<EntityId> {
+    match value {
+        SendPropValue::Integer(handle) if *handle != INVALID_HANDLE => {
+            Some(EntityId::from((*handle & HANDLE_INDEX_MASK) as u32))
+        }
+        _ => None,
+    }
+}
+
+/// The names of the server classes, e.g. `CTFPlayer`, learned from the data tables packet.
+#[derive(Default)]
+struct ServerClasses {
+    names: HashMap<ClassId, String>,
+}
+
+impl ServerClasses {
+    fn update(&mut self, packet: &Packet<'_>) {
+        if let Packet::DataTables(tables) = packet {
+            for class in &tables.server_classes {
+                self.names.insert(class.id, class.name.to_string());
+            }
+        }
+    }
+
+    fn name(&self, class: ClassId) -> Option<&str> {
+        self.names.get(&class).map(String::as_str)
+    }
+}
+
+fn player_by_entity(state: &GameState, entity: EntityId) -> Option<&Player> {
+    state.players.iter().find(|player| player.entity == entity)
+}
+
+fn player_by_user_id(state: &GameState, user_id: u16) -> Option<&Player> {
+    state.players.iter().find(|player| {
+        player
+            .info
+            .as_ref()
+            .is_some_and(|info| u16::from(info.user_id) == user_id)
+    })
+}
+
+/// Maps a team number as sent in game events to the team.
+fn team_from_number(team: u8) -> Option<Team> {
+    match team {
+        1 => Some(Team::Spectator),
+        2 => Some(Team::Red),
+        3 => Some(Team::Blue),
+        _ => None,
+    }
+}
+
+fn steam_id(player: &Player) -> Option<String> {
+    player.info.as_ref().map(|info| info.steam_id.clone())
+}
+
+fn name(player: &Player) -> Option<String> {
+    player.info.as_ref().map(|info| info.name.clone())
+}
@@ -0,0 +1,117 @@
+use super::{packet_entities, player_by_entity, steam_id, Extractor, ServerClasses, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tf_demo_parser::demo::{
+    message::packetentities::EntityId,
+    packet::Packet,
+    parser::gamestateanalyser::{GameState, Player},
+    sendprop::{SendPropIdentifier, SendPropValue},
+};
+
+/// A player's scoreboard points, written whenever they change.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct PlayerScore {
+    pub tick: u32,
+    pub steamid: Option<String>,
+    pub score: u32,
+}
+
+const PLAYER_RESOURCE_CLASS: &str = "CTFPlayerResource";
+/// The total score array is indexed by the player's entity index, 0 is unused.
+const SCORE_SLOTS: u32 = 34;
+
+/// Follows the player resource entity, which holds the scoreboard of every player.
+pub(super) struct ResourceScores {
+    score_props: Vec<SendPropIdentifier>,
+    classes: ServerClasses,
+    /// The latest score in each player entity's slot.
+    pub(super) scores: BTreeMap<u32, u32>,
+}
+
+impl ResourceScores {
+    pub(super) fn new() -> Self {
+        ResourceScores {
+            score_props: (0..SCORE_SLOTS)
+                .map(|slot| SendPropIdentifier::new("m_iTotalScore", &format!("{slot:03}")))
+                .collect(),
+            classes: ServerClasses::default(),
+            scores: BTreeMap::new(),
+        }
+    }
+
+    pub(super) fn update(&mut self, packet: &Packet<'_>) {
+        self.classes.update(packet);
+        for message in packet_entities(packet) {
+            for entity in &message.entities {
+                if self.classes.name(entity.server_class) != Some(PLAYER_RESOURCE_CLASS) {
+                    continue;
+                }
+                for prop in &entity.props {
+                    let Some(slot) = self
+                        .score_props
+                        .iter()
+                        .position(|score| *score == prop.identifier)
+                    else {
+                        continue;
+                    };
+                    if let SendPropValue::Integer(score) = prop.value {
+                        self.scores.insert(slot as u32, score.max(0) as u32);
+                    }
+                }
+            }
+        }
+    }
+
+    /// The players with a score, slots without a player hold whatever score their last player
+    /// left behind.
+    pub(super) fn players<'s>(
+        &'s self,
+        state: &'s GameState,
+    ) -> impl Iterator<Item = (u32, &'s Player, u32)> + 's {
+        self.scores.iter().filter_map(|(&slot, &score)| {
+            player_by_entity(state, EntityId::from(slot)).map(|player| (slot, player, score))
+        })
+    }
+}
+
+pub(super) struct PlayerScoreExtractor {
+    scores: ResourceScores,
+    /// The last score written for each player entity, to only write changes.
+    written: BTreeMap<u32, u32>,
+}
+
+impl PlayerScoreExtractor {
+    pub(super) fn new() -> Self {
+        PlayerScoreExtractor {
+            scores: ResourceScores::new(),
+            written: BTreeMap::new(),
+        }
+    }
+}
+
+impl Extractor for PlayerScoreExtractor {
+    fn on_packet(
+        &mut self,
+        _tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        self.scores.update(packet);
+    }
+
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        for (slot, player, score) in self.scores.players(state) {
+            if self.written.get(&slot) == Some(&score) {
+                continue;
+            }
+            records.push(StreamRecord::PlayerScore(PlayerScore {
+                tick,
+                steamid: steam_id(player),
+                score,
+            }));
+            self.written.insert(slot, score);
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use super::{
+    game_events, name, player_by_user_id, player_scores::ResourceScores, steam_id, Extractor,
+    StreamRecord,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent,
+    packet::Packet,
+    parser::gamestateanalyser::{Class, GameState, Team},
+};
+
+/// How long a player spent as one class.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ClassTime {
+    #[schemars(with = "crate::schema::Class")]
+    pub class: Class,
+    pub ticks: u32,
+}
+
+/// A player's final stats over the whole demo.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct PlayerSummary {
+    pub steamid: String,
+    pub name: Option<String>,
+    /// The team the player was last seen on.
+    #[schemars(with = "Option<crate::schema::Team>")]
+    pub team: Option<Team>,
+    /// The final scoreboard points.
+    pub score: u32,
+    pub kills: u32,
+    pub deaths: u32,
+    pub assists: u32,
+    /// In the order the classes were first played.
+    pub classes: Vec<ClassTime>,
+    /// The first tick the player was seen.
+    pub connect_tick: u32,
+    /// `None` if the player was still connected when the demo ended.
+    pub disconnect_tick: Option<u32>,
+}
+
+/// Aggregates the roster from the death and disconnect events, the scoreboard and the state.
+pub(super) struct PlayerSummaryExtractor {
+    scores: ResourceScores,
+    players: BTreeMap<String, PlayerSummary>,
+    /// The user id each disconnected player left with, a new one means they reconnected.
+    disconnected: HashMap<String, u16>,
+    last_tick: Option<u32>,
+}
+
+impl PlayerSummaryExtractor {
+    pub(super) fn new() -> Self {
+        PlayerSummaryExtractor {
+            scores: ResourceScores::new(),
+            players: BTreeMap::new(),
+            disconnected: HashMap::new(),
+            last_tick: None,
+        }
+    }
+
+    fn summary(&mut self, tick: u32, steamid: &str) -> &mut PlayerSummary {
+        self.players
+            .entry(steamid.to_string())
+            .or_insert_with(|| PlayerSummary {
+                steamid: steamid.to_string(),
+                name: None,
+                team: None,
+                score: 0,
+                kills: 0,
+                deaths: 0,
+                assists: 0,
+                classes: Vec::new(),
+                connect_tick: tick,
+                disconnect_tick: None,
+            })
+    }
+}
+
+impl Extractor for PlayerSummaryExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        self.scores.update(packet);
+        let resolve = |user_id: u16| player_by_user_id(state, user_id).and_then(steam_id);
+        for event in game_events(packet) {
+            match event {
+                GameEvent::PlayerDeath(death) => {
+                    if let Some(victim) = resolve(death.user_id) {
+                        self.summary(tick, &victim).deaths += 1;
+                    }
+                    if death.attacker != 0 && death.attacker != death.user_id {
+                        if let Some(attacker) = resolve(death.attacker) {
+                            self.summary(tick, &attacker).kills += 1;
+                        }
+                    }
+                    // No assist is sent as -1
+                    if !matches!(death.assister, 0 | u16::MAX) {
+                        if let Some(assister) = resolve(death.assister) {
+                            self.summary(tick, &assister).assists += 1;
+                        }
+                    }
+                }
+                GameEvent::PlayerDisconnect(disconnect) => {
+                    if let Some(steamid) = resolve(disconnect.user_id) {
+                        self.summary(tick, &steamid).disconnect_tick = Some(tick);
+                        self.disconnected.insert(steamid, disconnect.user_id);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn on_tick(&mut self, tick: u32, state: &GameState, _records: &mut Vec<StreamRecord>) {
+        // Ticks without a packet are skipped, so the time since the last one counts as one class
+        let elapsed = self.last_tick.map_or(0, |last| tick.saturating_sub(last));
+        self.last_tick = Some(tick);
+        for player in &state.players {
+            let Some(steamid) = steam_id(player) else {
+                continue;
+            };
+            // The state can hold on to players that left, anything after that isn't theirs
+            let user_id = player.info.as_ref().map(|info| u16::from(info.user_id));
+            match self.disconnected.get(&steamid) {
+                Some(&left) if Some(left) == user_id => continue,
+                Some(_) => {
+                    self.disconnected.remove(&steamid);
+                    self.summary(tick, &steamid).disconnect_tick = None;
+                }
+                None => {}
+            }
+            let summary = self.summary(tick, &steamid);
+            if let Some(name) = name(player) {
+                summary.name = Some(name);
+            }
+            summary.team = Some(player.team);
+            if player.class == Class::Other || elapsed == 0 {
+                continue;
+            }
+            match summary
+                .classes
+                .iter_mut()
+                .find(|time| time.class == player.class)
+            {
+                Some(time) => time.ticks += elapsed,
+                None => summary.classes.push(ClassTime {
+                    class: player.class,
+                    ticks: elapsed,
+                }),
+            }
+        }
+        let scores: Vec<_> = self
+            .scores
+            .players(state)
+            .filter_map(|(_, player, score)| steam_id(player).map(|steamid| (steamid, score)))
+            .collect();
+        for (steamid, score) in scores {
+            if self.disconnected.contains_key(&steamid) {
+                continue;
+            }
+            self.summary(tick, &steamid).score = score;
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        records.extend(
+            std::mem::take(&mut self.players)
+                .into_values()
+                .map(StreamRecord::PlayerSummary),
+        );
+    }
+}
@@ -0,0 +1,135 @@
+use super::{steam_id, Extractor, StreamOptions, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tf_demo_parser::demo::{
+    message::packetentities::EntityId,
+    parser::gamestateanalyser::{Class, GameState, PlayerState, Team},
+};
+
+/// One player's position at a sampled tick, flat so it maps directly onto a table row.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Position {
+    pub tick: u32,
+    pub steamid: Option<String>,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    #[schemars(with = "crate::schema::Team")]
+    pub team: Team,
+    #[schemars(with = "crate::schema::Class")]
+    pub class: Class,
+    pub alive: bool,
+    /// Filled in between two updates with `--interpolate-positions`, rather than sent.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub interpolated: bool,
+}
+
+/// The longest a position can go without changing and still be interpolated, a third of a
+/// second at 66 ticks per second. Players standing still aren't updated either, it's only
+/// worth telling apart for short gaps.
+const MAX_INTERPOLATION_TICKS: u32 = 22;
+
+/// The samples of one player since their position last changed.
+#[derive(Default)]
+struct Gap {
+    /// The last sample with a new position.
+    known: Option<Position>,
+    held: Vec<Position>,
+}
+
+impl Gap {
+    /// Takes the next sample, returning the ones that are ready to be written.
+    fn sample(&mut self, position: Position, records: &mut Vec<StreamRecord>) {
+        let Some(known) = &self.known else {
+            self.known = Some(position.clone());
+            records.push(StreamRecord::Position(position));
+            return;
+        };
+        let moved = (known.x, known.y, known.z) != (position.x, position.y, position.z);
+        let span = position.tick - known.tick;
+        if !moved && span <= MAX_INTERPOLATION_TICKS {
+            self.held.push(position);
+            return;
+        }
+        // A respawn or a long gap isn't motion, the held samples stand as they are
+        let interpolate = moved
+            && span <= MAX_INTERPOLATION_TICKS
+            && known.alive
+            && position.alive
+            && self.held.iter().all(|held| held.alive);
+        for mut held in self.held.drain(..) {
+            if interpolate {
+                let t = (held.tick - known.tick) as f32 / span as f32;
+                held.x = known.x + (position.x - known.x) * t;
+                held.y = known.y + (position.y - known.y) * t;
+                held.z = known.z + (position.z - known.z) * t;
+                held.interpolated = true;
+            }
+            records.push(StreamRecord::Position(held));
+        }
+        if moved {
+            self.known = Some(position.clone());
+        }
+        records.push(StreamRecord::Position(position));
+    }
+
+    fn flush(&mut self, records: &mut Vec<StreamRecord>) {
+        records.extend(self.held.drain(..).map(StreamRecord::Position));
+    }
+}
+
+pub(super) struct PositionExtractor {
+    stride: u32,
+    ticks_seen: u32,
+    /// Only set with `--interpolate-positions`. Held samples are written once the gap closes,
+    /// so the records are then in tick order per player rather than overall. Ordered by entity
+    /// so the flush at the end writes them in the same order every run.
+    gaps: Option<BTreeMap<EntityId, Gap>>,
+}
+
+impl PositionExtractor {
+    pub(super) fn new(options: &StreamOptions) -> Self {
+        PositionExtractor {
+            stride: options.position_stride.max(1),
+            ticks_seen: 0,
+            gaps: options.interpolate_positions.then(BTreeMap::new),
+        }
+    }
+}
+
+impl Extractor for PositionExtractor {
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        let sampled = self.ticks_seen % self.stride == 0;
+        self.ticks_seen += 1;
+        if !sampled {
+            return;
+        }
+        for player in &state.players {
+            let position = Position {
+                tick,
+                steamid: steam_id(player),
+                x: player.position.x,
+                y: player.position.y,
+                z: player.position.z,
+                team: player.team,
+                class: player.class,
+                alive: player.state == PlayerState::Alive,
+                interpolated: false,
+            };
+            match self.gaps.as_mut() {
+                Some(gaps) => gaps
+                    .entry(player.entity)
+                    .or_default()
+                    .sample(position, records),
+                None => records.push(StreamRecord::Position(position)),
+            }
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        for gap in self.gaps.iter_mut().flat_map(BTreeMap::values_mut) {
+            gap.flush(records);
+        }
+    }
+}
@@ -0,0 +1,135 @@
+use super::{
+    handle_target, packet_entities, player_by_entity, steam_id, Extractor, ServerClasses,
+    StreamRecord,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::{
+    message::packetentities::{EntityId, UpdateType},
+    packet::Packet,
+    parser::gamestateanalyser::GameState,
+    sendprop::{SendPropIdentifier, SendPropValue},
+    vector::Vector,
+};
+
+/// A projectile from the tick it was created to the tick it was removed.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Projectile {
+    pub spawn_tick: u32,
+    /// `None` if the projectile still existed when the demo ended.
+    pub despawn_tick: Option<u32>,
+    pub owner_steamid: Option<String>,
+    /// The projectile's server class, e.g. `CTFProjectile_Rocket`.
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[schemars(with = "Option<crate::schema::Vector>")]
+    pub spawn_pos: Option<Vector>,
+}
+
+const OWNER: SendPropIdentifier = SendPropIdentifier::new("DT_BaseEntity", "m_hOwnerEntity");
+/// Grenades like pipes and stickies set their thrower rather than an owner.
+const THROWER: SendPropIdentifier = SendPropIdentifier::new("DT_BaseGrenade", "m_hThrower");
+/// Projectiles send their origin from their own tables rather than the base entity's.
+const ORIGINS: [SendPropIdentifier; 3] = [
+    SendPropIdentifier::new("DT_BaseEntity", "m_vecOrigin"),
+    SendPropIdentifier::new("DT_TFBaseRocket", "m_vecOrigin"),
+    SendPropIdentifier::new("DT_TFWeaponBaseGrenadeProj", "m_vecOrigin"),
+];
+
+struct Tracked {
+    serial: u32,
+    projectile: Projectile,
+}
+
+/// Follows the creation and deletion of the projectile entities.
+#[derive(Default)]
+pub(super) struct ProjectileExtractor {
+    classes: ServerClasses,
+    live: HashMap<EntityId, Tracked>,
+}
+
+impl ProjectileExtractor {
+    fn despawn(&mut self, entity: EntityId, tick: Option<u32>, records: &mut Vec<StreamRecord>) {
+        if let Some(mut tracked) = self.live.remove(&entity) {
+            tracked.projectile.despawn_tick = tick;
+            records.push(StreamRecord::Projectile(tracked.projectile));
+        }
+    }
+}
+
+impl Extractor for ProjectileExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        self.classes.update(packet);
+        for message in packet_entities(packet) {
+            for entity in &message.entities {
+                let id = entity.entity_index;
+                match entity.update_type {
+                    UpdateType::Delete => {
+                        self.despawn(id, Some(tick), records);
+                        continue;
+                    }
+                    // The slot was reused, so the projectile that had it is gone
+                    UpdateType::Enter
+                        if self
+                            .live
+                            .get(&id)
+                            .is_some_and(|tracked| tracked.serial != entity.serial_number) =>
+                    {
+                        self.despawn(id, Some(tick), records);
+                    }
+                    _ => {}
+                }
+                let Some(class) = self.classes.name(entity.server_class) else {
+                    continue;
+                };
+                if !class.contains("Projectile") {
+                    continue;
+                }
+                let tracked = self.live.entry(id).or_insert_with(|| Tracked {
+                    serial: entity.serial_number,
+                    projectile: Projectile {
+                        spawn_tick: tick,
+                        despawn_tick: None,
+                        owner_steamid: None,
+                        kind: class.to_string(),
+                        spawn_pos: None,
+                    },
+                });
+                // Only the first values are kept, later updates are the projectile flying
+                for prop in &entity.props {
+                    let projectile = &mut tracked.projectile;
+                    if prop.identifier == OWNER || prop.identifier == THROWER {
+                        if projectile.owner_steamid.is_none() {
+                            projectile.owner_steamid = handle_target(&prop.value)
+                                .and_then(|owner| player_by_entity(state, owner))
+                                .and_then(steam_id);
+                        }
+                    } else if ORIGINS.contains(&prop.identifier) {
+                        if let (None, SendPropValue::Vector(origin)) =
+                            (projectile.spawn_pos, &prop.value)
+                        {
+                            projectile.spawn_pos = Some(*origin);
+                        }
+                    }
+                }
+            }
+            for &id in &message.removed_entities {
+                self.despawn(id, Some(tick), records);
+            }
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        let live: Vec<EntityId> = self.live.keys().copied().collect();
+        for entity in live {
+            self.despawn(entity, None, records);
+        }
+    }
+}
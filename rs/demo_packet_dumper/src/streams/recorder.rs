@@ -0,0 +1,90 @@
+use super::{messages, player_by_entity, steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tf_demo_parser::demo::{
+    message::{packetentities::EntityId, Message},
+    packet::Packet,
+    parser::gamestateanalyser::GameState,
+};
+
+/// The player recording a POV demo at one tick, with the view angles of their own client.
+///
+/// Only the recorder's client knows where they were really looking, the angles of the other
+/// players are the coarser ones the server networks.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Recorder {
+    pub tick: u32,
+    #[schemars(with = "u32")]
+    pub entity_id: EntityId,
+    pub steamid: Option<String>,
+    /// Always `true`, marks these as the recorder's frames when joined with the GameState.
+    pub is_recorder: bool,
+    pub pitch: f32,
+    pub yaw: f32,
+}
+
+/// Finds the recorder from the server info, which tells a POV client which player slot is its own.
+/// SourceTV demos have no recorder.
+#[derive(Default)]
+pub(super) struct RecorderView {
+    recorder: Option<EntityId>,
+    /// Pitch and yaw as of the latest packet.
+    angles: Option<(f32, f32)>,
+}
+
+impl RecorderView {
+    pub(super) fn update(&mut self, packet: &Packet<'_>) {
+        if let Packet::Message(message) = packet {
+            let angles = message.meta.view_angles.local_angles[0];
+            self.angles = Some((angles.x, angles.y));
+        }
+        for message in messages(packet) {
+            if let Message::ServerInfo(info) = message {
+                // Player entities come after the world entity, so slot 0 is entity 1
+                self.recorder =
+                    (!info.stv).then(|| EntityId::from(u32::from(info.player_slot) + 1));
+            }
+        }
+    }
+
+    /// The recorder's entity with their latest pitch and yaw, once both are known.
+    pub(super) fn angles(&self) -> Option<(EntityId, f32, f32)> {
+        let (recorder, (pitch, yaw)) = (self.recorder?, self.angles?);
+        Some((recorder, pitch, yaw))
+    }
+}
+
+/// Nothing is written for SourceTV demos.
+#[derive(Default)]
+pub(super) struct RecorderExtractor {
+    view: RecorderView,
+}
+
+impl Extractor for RecorderExtractor {
+    fn on_packet(
+        &mut self,
+        _tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        self.view.update(packet);
+    }
+
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        let Some((recorder, pitch, yaw)) = self.view.angles() else {
+            return;
+        };
+        let Some(player) = player_by_entity(state, recorder) else {
+            return;
+        };
+        records.push(StreamRecord::Recorder(Recorder {
+            tick,
+            entity_id: recorder,
+            steamid: steam_id(player),
+            is_recorder: true,
+            pitch,
+            yaw,
+        }));
+    }
+}
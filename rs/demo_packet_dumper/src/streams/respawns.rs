@@ -0,0 +1,113 @@
+use super::{steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::{
+    message::packetentities::EntityId,
+    parser::gamestateanalyser::{GameState, Player, PlayerState},
+    vector::Vector,
+};
+
+/// How long a player spent dead and then in spawn, for one life.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct RespawnTiming {
+    pub steamid: Option<String>,
+    /// `None` for the player's first spawn in the demo.
+    pub death_tick: Option<u32>,
+    pub respawn_tick: u32,
+    /// `None` when the player died again, or the demo ended, before leaving spawn.
+    pub left_spawn_tick: Option<u32>,
+}
+
+/// How far from where they spawned a player has to get to count as out of the spawn room.
+///
+/// The respawn room triggers aren't part of the GameState, so this stands in for them. It's
+/// about the depth of a typical spawn room, past its doors for most maps.
+const SPAWN_EXIT_DISTANCE: f32 = 768.0;
+
+/// A life that's waiting for the player to leave spawn.
+struct Spawned {
+    death_tick: Option<u32>,
+    respawn_tick: u32,
+    /// The position when the player became alive, which can still be where they died as the
+    /// position isn't always sent on the same tick.
+    stale: Vector,
+    /// Where they spawned, once their position was updated.
+    origin: Option<Vector>,
+}
+
+#[derive(Default)]
+struct Life {
+    alive: bool,
+    death_tick: Option<u32>,
+    spawned: Option<Spawned>,
+}
+
+fn distance(a: Vector, b: Vector) -> f32 {
+    ((a.x - b.x).powi(2) + (a.y - b.y).powi(2) + (a.z - b.z).powi(2)).sqrt()
+}
+
+fn timing(player: &Player, spawned: Spawned, left_spawn_tick: Option<u32>) -> StreamRecord {
+    StreamRecord::RespawnTiming(RespawnTiming {
+        steamid: steam_id(player),
+        death_tick: spawned.death_tick,
+        respawn_tick: spawned.respawn_tick,
+        left_spawn_tick,
+    })
+}
+
+/// Follows each player from dying to respawning to leaving spawn. A life is written once the
+/// player has left spawn, or when it ends without them having left.
+#[derive(Default)]
+pub(super) struct RespawnExtractor {
+    players: HashMap<EntityId, Life>,
+}
+
+impl Extractor for RespawnExtractor {
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        for player in &state.players {
+            let life = self.players.entry(player.entity).or_default();
+            let alive = player.state == PlayerState::Alive;
+            let position = player.position;
+            if alive && !life.alive {
+                life.spawned = Some(Spawned {
+                    death_tick: life.death_tick.take(),
+                    respawn_tick: tick,
+                    stale: position,
+                    origin: None,
+                });
+            } else if !alive && life.alive {
+                life.death_tick = Some(tick);
+                if let Some(spawned) = life.spawned.take() {
+                    records.push(timing(player, spawned, None));
+                }
+            }
+            life.alive = alive;
+            let Some(spawned) = life.spawned.as_mut().filter(|_| alive) else {
+                continue;
+            };
+            let origin = match spawned.origin {
+                Some(origin) => origin,
+                None if position != spawned.stale => *spawned.origin.insert(position),
+                None => continue,
+            };
+            if distance(origin, position) > SPAWN_EXIT_DISTANCE {
+                if let Some(spawned) = life.spawned.take() {
+                    records.push(timing(player, spawned, Some(tick)));
+                }
+            }
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        for player in &state.players {
+            if let Some(spawned) = self
+                .players
+                .get_mut(&player.entity)
+                .and_then(|life| life.spawned.take())
+            {
+                records.push(timing(player, spawned, None));
+            }
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use super::{game_events, team_from_number, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent,
+    packet::Packet,
+    parser::gamestateanalyser::{GameState, Team},
+};
+
+/// One round of the match, from its start to the win or stalemate that ended it.
+///
+/// The score is the number of rounds each team had won by the end of the round.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Round {
+    pub index: u32,
+    pub start_tick: u32,
+    pub end_tick: u32,
+    /// `None` for a stalemate, or a round the demo ends in.
+    #[schemars(with = "Option<crate::schema::Team>")]
+    pub winner: Option<Team>,
+    pub win_reason: Option<u8>,
+    pub red_score: u32,
+    pub blue_score: u32,
+    /// The tick setup time ended, for maps with a setup phase.
+    pub setup_end_tick: Option<u32>,
+    pub sudden_death: bool,
+    /// The round started while waiting for players, so it doesn't count towards the match.
+    pub waiting_for_players: bool,
+    /// The demo started or ended part way through this round.
+    pub partial: bool,
+}
+
+#[derive(Default)]
+pub(super) struct RoundExtractor {
+    current: Option<Round>,
+    next_index: u32,
+    first_tick: Option<u32>,
+    red_score: u32,
+    blue_score: u32,
+    waiting_for_players: bool,
+}
+
+impl RoundExtractor {
+    fn start_round(&mut self, tick: u32, partial: bool) -> Round {
+        self.next_index += 1;
+        Round {
+            index: self.next_index - 1,
+            start_tick: tick,
+            end_tick: tick,
+            winner: None,
+            win_reason: None,
+            red_score: self.red_score,
+            blue_score: self.blue_score,
+            setup_end_tick: None,
+            sudden_death: false,
+            waiting_for_players: self.waiting_for_players,
+            partial,
+        }
+    }
+
+    /// The round in progress, assuming one started before the demo did if none is known.
+    fn current_round(&mut self, tick: u32) -> &mut Round {
+        if self.current.is_none() {
+            let start = self.first_tick.unwrap_or(tick);
+            self.current = Some(self.start_round(start, true));
+        }
+        self.current.as_mut().unwrap()
+    }
+}
+
+impl Extractor for RoundExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        self.first_tick.get_or_insert(tick);
+        for event in game_events(packet) {
+            match event {
+                GameEvent::TeamPlayWaitingBegins(_) => self.waiting_for_players = true,
+                GameEvent::TeamPlayWaitingEnds(_) => self.waiting_for_players = false,
+                GameEvent::TeamPlayRoundStart(_) => {
+                    // A restart without a result, e.g. leaving waiting for players
+                    if let Some(mut round) = self.current.take() {
+                        round.end_tick = tick;
+                        records.push(StreamRecord::Round(round));
+                    }
+                    self.current = Some(self.start_round(tick, false));
+                }
+                GameEvent::TeamPlaySetupFinished(_) => {
+                    self.current_round(tick).setup_end_tick = Some(tick);
+                }
+                GameEvent::TeamPlayRoundWin(win) => {
+                    let winner = team_from_number(win.team);
+                    match winner {
+                        Some(Team::Red) => self.red_score += 1,
+                        Some(Team::Blue) => self.blue_score += 1,
+                        _ => {}
+                    }
+                    let (red_score, blue_score) = (self.red_score, self.blue_score);
+                    let round = self.current_round(tick);
+                    round.end_tick = tick;
+                    round.winner = winner;
+                    round.win_reason = Some(win.win_reason);
+                    round.sudden_death = win.was_sudden_death != 0;
+                    round.red_score = red_score;
+                    round.blue_score = blue_score;
+                    records.extend(self.current.take().map(StreamRecord::Round));
+                }
+                GameEvent::TeamPlayRoundStalemate(_) => {
+                    let round = self.current_round(tick);
+                    round.end_tick = tick;
+                    records.extend(self.current.take().map(StreamRecord::Round));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn finish(&mut self, tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        if let Some(mut round) = self.current.take() {
+            round.end_tick = tick;
+            round.partial = true;
+            records.push(StreamRecord::Round(round));
+        }
+    }
+}
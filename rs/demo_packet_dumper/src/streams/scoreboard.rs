@@ -0,0 +1,136 @@
+use super::{
+    game_events, packet_entities, team_from_number, Extractor, ServerClasses, StreamRecord,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent,
+    message::packetentities::{EntityId, UpdateType},
+    packet::Packet,
+    parser::gamestateanalyser::{GameState, Team},
+    sendprop::{SendPropIdentifier, SendPropValue},
+};
+
+/// What moved the scoreboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoreEvent {
+    /// A team's score changed, e.g. a KOTH mini-round or a payload stage being won.
+    ScoreChanged,
+    /// Both scores went back to zero, as on a tournament restart.
+    ScoreReset,
+    RoundWin,
+    PointCaptured,
+}
+
+/// The scores as of a change to the scoreboard.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ScoreUpdate {
+    pub tick: u32,
+    pub blu_score: u32,
+    pub red_score: u32,
+    pub event: ScoreEvent,
+    /// The team that scored, won or captured.
+    #[schemars(with = "Option<crate::schema::Team>")]
+    pub team: Option<Team>,
+    /// The name of the captured point.
+    pub point: Option<String>,
+}
+
+const TEAM_NUMBER: SendPropIdentifier = SendPropIdentifier::new("DT_Team", "m_iTeamNum");
+const TEAM_SCORE: SendPropIdentifier = SendPropIdentifier::new("DT_Team", "m_iScore");
+
+/// Follows the scores of the team entities, along with the events that change them.
+#[derive(Default)]
+pub(super) struct ScoreboardExtractor {
+    classes: ServerClasses,
+    /// The team number of each team entity.
+    teams: HashMap<EntityId, u8>,
+    red_score: u32,
+    blu_score: u32,
+}
+
+impl ScoreboardExtractor {
+    fn update(&self, tick: u32, event: ScoreEvent, team: Option<Team>) -> ScoreUpdate {
+        ScoreUpdate {
+            tick,
+            blu_score: self.blu_score,
+            red_score: self.red_score,
+            event,
+            team,
+            point: None,
+        }
+    }
+}
+
+impl Extractor for ScoreboardExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        self.classes.update(packet);
+        for message in packet_entities(packet) {
+            for entity in &message.entities {
+                if self.classes.name(entity.server_class) != Some("CTFTeam") {
+                    continue;
+                }
+                if entity.update_type == UpdateType::Delete {
+                    self.teams.remove(&entity.entity_index);
+                    continue;
+                }
+                let mut score = None;
+                for prop in &entity.props {
+                    match prop.value {
+                        SendPropValue::Integer(number) if prop.identifier == TEAM_NUMBER => {
+                            self.teams.insert(entity.entity_index, number as u8);
+                        }
+                        SendPropValue::Integer(value) if prop.identifier == TEAM_SCORE => {
+                            score = Some(value.max(0) as u32);
+                        }
+                        _ => {}
+                    }
+                }
+                let (Some(score), Some(&number)) = (score, self.teams.get(&entity.entity_index))
+                else {
+                    continue;
+                };
+                let team = team_from_number(number);
+                let previous = match team {
+                    Some(Team::Red) => &mut self.red_score,
+                    Some(Team::Blue) => &mut self.blu_score,
+                    _ => continue,
+                };
+                if *previous == score {
+                    continue;
+                }
+                *previous = score;
+                let update = if self.red_score == 0 && self.blu_score == 0 {
+                    self.update(tick, ScoreEvent::ScoreReset, None)
+                } else {
+                    self.update(tick, ScoreEvent::ScoreChanged, team)
+                };
+                records.push(StreamRecord::Score(update));
+            }
+        }
+        for event in game_events(packet) {
+            match event {
+                GameEvent::TeamPlayRoundWin(win) => {
+                    let team = team_from_number(win.team);
+                    let update = self.update(tick, ScoreEvent::RoundWin, team);
+                    records.push(StreamRecord::Score(update));
+                }
+                GameEvent::TeamPlayPointCaptured(capture) => {
+                    let team = team_from_number(capture.team);
+                    let mut update = self.update(tick, ScoreEvent::PointCaptured, team);
+                    update.point = Some(capture.cp_name.to_string());
+                    records.push(StreamRecord::Score(update));
+                }
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use super::{messages, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tf_demo_parser::demo::{
+    message::Message, packet::Packet, parser::gamestateanalyser::GameState,
+};
+
+/// A replicated convar changing after the client connected, e.g. `sv_cheats` being turned on.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct ConVarChange {
+    pub tick: u32,
+    pub name: String,
+    pub value: String,
+}
+
+/// The server the demo was recorded on and how it was set up, as it told the client on
+/// connecting.
+#[derive(Debug, Clone, Default, Serialize, JsonSchema)]
+pub struct ServerSettings {
+    pub server_name: Option<String>,
+    pub map: Option<String>,
+    pub game: Option<String>,
+    /// The network protocol version of the server.
+    pub version: Option<u16>,
+    pub dedicated: Option<bool>,
+    /// Whether the demo was recorded by SourceTV rather than a player.
+    pub stv: Option<bool>,
+    pub max_players: Option<u8>,
+    pub interval_per_tick: Option<f32>,
+    /// The replicated convars as of connecting, e.g. `mp_tournament` or `mp_maxrounds`. The
+    /// server only sends the ones that aren't at their default.
+    pub convars: BTreeMap<String, String>,
+    /// The convars that changed later on, in order.
+    pub changed: Vec<ConVarChange>,
+}
+
+#[derive(Default)]
+pub(super) struct ServerInfoExtractor {
+    settings: ServerSettings,
+}
+
+impl Extractor for ServerInfoExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        let signon = matches!(packet, Packet::Signon(_));
+        for message in messages(packet) {
+            match message {
+                Message::ServerInfo(info) => {
+                    let settings = &mut self.settings;
+                    settings.server_name = Some(info.server_name.clone());
+                    settings.map = Some(info.map.clone());
+                    settings.game = Some(info.game.clone());
+                    settings.version = Some(info.version);
+                    settings.dedicated = Some(info.dedicated);
+                    settings.stv = Some(info.stv);
+                    settings.max_players = Some(info.max_player_count);
+                    settings.interval_per_tick = Some(info.interval_per_tick);
+                }
+                Message::SetConVar(message) => {
+                    for var in &message.vars {
+                        if signon {
+                            self.settings
+                                .convars
+                                .insert(var.key.clone(), var.value.clone());
+                        } else {
+                            self.settings.changed.push(ConVarChange {
+                                tick,
+                                name: var.key.clone(),
+                                value: var.value.clone(),
+                            });
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        records.push(StreamRecord::ServerSettings(std::mem::take(
+            &mut self.settings,
+        )));
+    }
+}
@@ -0,0 +1,136 @@
+use super::{messages, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::{collections::BTreeMap, fmt::Write};
+use tf_demo_parser::demo::{
+    message::Message,
+    packet::{
+        stringtable::{StringTable as ParsedTable, StringTableEntry},
+        Packet,
+    },
+    parser::gamestateanalyser::GameState,
+};
+
+/// One entry of a string table.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct StringTableItem {
+    pub index: u16,
+    pub text: Option<String>,
+    /// The entry's user data as hex, e.g. the player info in `userinfo` or the entity baselines
+    /// in `instancebaseline`.
+    pub data: Option<String>,
+}
+
+/// A string table as of the end of the demo, with every update applied.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct StringTable {
+    pub name: String,
+    pub max_entries: u16,
+    pub entries: Vec<StringTableItem>,
+}
+
+fn item(index: u16, entry: &StringTableEntry<'_>) -> StringTableItem {
+    let data = entry.extra_data.as_ref().map(|extra| {
+        let mut stream = extra.data.clone();
+        let bytes = stream
+            .read_bytes(stream.bits_left() / 8)
+            .unwrap_or_default();
+        bytes.iter().fold(String::new(), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+    });
+    StringTableItem {
+        index,
+        text: entry.text.as_ref().map(|text| text.as_ref().to_string()),
+        data,
+    }
+}
+
+/// Builds up the string tables from their creation and updates, in the order they were created
+/// in since updates refer to them by that position.
+#[derive(Default)]
+pub(super) struct StringTableExtractor {
+    tables: Vec<(StringTable, BTreeMap<u16, StringTableItem>)>,
+}
+
+fn items(table: &ParsedTable<'_>) -> BTreeMap<u16, StringTableItem> {
+    table
+        .entries
+        .iter()
+        .map(|(index, entry)| (*index, item(*index, entry)))
+        .collect()
+}
+
+impl StringTableExtractor {
+    fn create(&mut self, table: &ParsedTable<'_>) {
+        let entries = items(table);
+        let table = StringTable {
+            name: table.name.to_string(),
+            max_entries: table.max_entries,
+            entries: Vec::new(),
+        };
+        self.tables.push((table, entries));
+    }
+
+    /// A `dem_stringtables` packet holds the whole of tables that were already created, so they
+    /// replace the ones of the same name, keeping their position for the updates after it.
+    fn snapshot(&mut self, table: &ParsedTable<'_>) {
+        let existing = self
+            .tables
+            .iter_mut()
+            .find(|(existing, _)| *existing.name == *table.name);
+        match existing {
+            Some((existing, entries)) => {
+                existing.max_entries = table.max_entries;
+                *entries = items(table);
+            }
+            None => self.create(table),
+        }
+    }
+
+    fn update(&mut self, table_id: usize, entries: &[(u16, StringTableEntry<'_>)]) {
+        let Some((_, items)) = self.tables.get_mut(table_id) else {
+            return;
+        };
+        for (index, entry) in entries {
+            items.insert(*index, item(*index, entry));
+        }
+    }
+}
+
+impl Extractor for StringTableExtractor {
+    fn on_packet(
+        &mut self,
+        _tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        if let Packet::StringTables(packet) = packet {
+            for table in &packet.tables {
+                self.snapshot(table);
+            }
+        }
+        for message in messages(packet) {
+            match message {
+                Message::CreateStringTable(message) => self.create(&message.table),
+                Message::UpdateStringTable(message) => {
+                    self.update(usize::from(message.table_id), &message.entries)
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn finish(&mut self, _tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        records.extend(
+            std::mem::take(&mut self.tables)
+                .into_iter()
+                .map(|(mut table, items)| {
+                    table.entries = items.into_values().collect();
+                    StreamRecord::StringTable(table)
+                }),
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use super::{game_events, player_by_user_id, steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::{
+    gameevent_gen::GameEvent,
+    message::packetentities::EntityId,
+    packet::Packet,
+    parser::gamestateanalyser::{GameState, Player, Team},
+};
+
+/// A player joining a team, spectating or leaving the server.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct TeamChange {
+    pub tick: u32,
+    pub steamid: Option<String>,
+    /// `None` the first time the player is seen.
+    #[schemars(with = "Option<crate::schema::Team>")]
+    pub from_team: Option<Team>,
+    /// `None` when the player disconnected.
+    #[schemars(with = "Option<crate::schema::Team>")]
+    pub to_team: Option<Team>,
+}
+
+struct Seen {
+    team: Team,
+    user_id: Option<u16>,
+}
+
+fn user_id(player: &Player) -> Option<u16> {
+    player.info.as_ref().map(|info| u16::from(info.user_id))
+}
+
+/// Follows each player's team across the states, starting from the team they're first seen on.
+#[derive(Default)]
+pub(super) struct TeamChangeExtractor {
+    players: HashMap<EntityId, Seen>,
+    /// The user id that last left each player slot. The state can hold on to players that left,
+    /// so they're only seen again once someone else, or them reconnecting, takes the slot.
+    left: HashMap<EntityId, Option<u16>>,
+}
+
+impl Extractor for TeamChangeExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        for event in game_events(packet) {
+            let GameEvent::PlayerDisconnect(disconnect) = event else {
+                continue;
+            };
+            let Some(player) = player_by_user_id(state, disconnect.user_id) else {
+                continue;
+            };
+            let Some(seen) = self.players.remove(&player.entity) else {
+                continue;
+            };
+            self.left.insert(player.entity, seen.user_id);
+            records.push(StreamRecord::TeamChange(TeamChange {
+                tick,
+                steamid: steam_id(player),
+                from_team: Some(seen.team),
+                to_team: None,
+            }));
+        }
+    }
+
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        for player in &state.players {
+            // Not told who they are yet, the record would be anonymous
+            if player.info.is_none() {
+                continue;
+            }
+            let user_id = user_id(player);
+            if self.left.get(&player.entity) == Some(&user_id) {
+                continue;
+            }
+            self.left.remove(&player.entity);
+            let seen = self.players.insert(
+                player.entity,
+                Seen {
+                    team: player.team,
+                    user_id,
+                },
+            );
+            // A new user in the slot means the previous one left without an event
+            let from_team = match seen {
+                Some(seen) if seen.user_id == user_id => Some(seen.team),
+                _ => None,
+            };
+            if from_team != Some(player.team) {
+                records.push(StreamRecord::TeamChange(TeamChange {
+                    tick,
+                    steamid: steam_id(player),
+                    from_team,
+                    to_team: Some(player.team),
+                }));
+            }
+        }
+    }
+}
@@ -0,0 +1,175 @@
+use super::{
+    handle_target, packet_entities, player_by_entity, steam_id, Extractor, ServerClasses,
+    StreamRecord,
+};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tf_demo_parser::demo::{
+    message::packetentities::{EntityId, UpdateType},
+    packet::Packet,
+    parser::gamestateanalyser::GameState,
+    sendprop::{SendPropIdentifier, SendPropValue},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UberEvent {
+    /// The charge or its state changed.
+    Charge,
+    /// The medic popped the charge, written once before the charge starts draining.
+    Deployed,
+}
+
+/// Which medigun the charge is for, as they all charge the same but do different things.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum UberType {
+    Uber,
+    Kritzkrieg,
+    QuickFix,
+    Vaccinator,
+}
+
+impl UberType {
+    fn from_item_definition(index: Option<u32>) -> Self {
+        match index {
+            Some(35) => UberType::Kritzkrieg,
+            Some(411) => UberType::QuickFix,
+            Some(998) => UberType::Vaccinator,
+            // The stock medigun and all of its reskins
+            _ => UberType::Uber,
+        }
+    }
+}
+
+/// A medic's ubercharge, written whenever it changes.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct Ubercharge {
+    pub tick: u32,
+    #[serde(rename = "type")]
+    pub kind: UberEvent,
+    pub medic_steamid: Option<String>,
+    /// 0 to 100.
+    pub charge_pct: f32,
+    /// The charge is being used and draining.
+    pub is_popped: bool,
+    pub uber_type: UberType,
+}
+
+const MEDIGUN_CLASS: &str = "CWeaponMedigun";
+const OWNER: SendPropIdentifier = SendPropIdentifier::new("DT_BaseCombatWeapon", "m_hOwner");
+/// The owner gets the charge from the local table, everyone else from the non-local one.
+const CHARGE_LEVELS: [SendPropIdentifier; 2] = [
+    SendPropIdentifier::new("DT_LocalTFWeaponMedigunData", "m_flChargeLevel"),
+    SendPropIdentifier::new("DT_TFWeaponMedigunDataNonLocal", "m_flChargeLevel"),
+];
+const CHARGE_RELEASE: SendPropIdentifier =
+    SendPropIdentifier::new("DT_WeaponMedigun", "m_bChargeRelease");
+const ITEM_DEFINITION_INDEX: SendPropIdentifier =
+    SendPropIdentifier::new("DT_ScriptCreatedItem", "m_iItemDefinitionIndex");
+
+#[derive(Default)]
+struct Medigun {
+    serial: u32,
+    owner: Option<EntityId>,
+    item_definition_index: Option<u32>,
+    charge: f32,
+    popped: bool,
+    /// The last charge and state written, to only write changes.
+    written: Option<(f32, bool)>,
+}
+
+/// Follows the charge props of the medigun entities.
+#[derive(Default)]
+pub(super) struct UberchargeExtractor {
+    classes: ServerClasses,
+    /// Ordered by entity, so the records of a tick come out in the same order every run.
+    mediguns: BTreeMap<EntityId, Medigun>,
+}
+
+impl Extractor for UberchargeExtractor {
+    fn on_packet(
+        &mut self,
+        _tick: u32,
+        packet: &Packet<'_>,
+        _state: &GameState,
+        _records: &mut Vec<StreamRecord>,
+    ) {
+        self.classes.update(packet);
+        for message in packet_entities(packet) {
+            for entity in &message.entities {
+                let id = entity.entity_index;
+                if entity.update_type == UpdateType::Delete {
+                    self.mediguns.remove(&id);
+                    continue;
+                }
+                if self.classes.name(entity.server_class) != Some(MEDIGUN_CLASS) {
+                    // The slot may have been reused by something else
+                    self.mediguns.remove(&id);
+                    continue;
+                }
+                // A new medigun in the slot starts over rather than continuing the old charge
+                if self
+                    .mediguns
+                    .get(&id)
+                    .is_some_and(|medigun| medigun.serial != entity.serial_number)
+                {
+                    self.mediguns.remove(&id);
+                }
+                let medigun = self.mediguns.entry(id).or_insert_with(|| Medigun {
+                    serial: entity.serial_number,
+                    ..Medigun::default()
+                });
+                for prop in &entity.props {
+                    if prop.identifier == OWNER {
+                        medigun.owner = handle_target(&prop.value);
+                    } else if CHARGE_LEVELS.contains(&prop.identifier) {
+                        if let SendPropValue::Float(charge) = prop.value {
+                            medigun.charge = charge;
+                        }
+                    } else if prop.identifier == CHARGE_RELEASE {
+                        if let SendPropValue::Integer(release) = prop.value {
+                            medigun.popped = release != 0;
+                        }
+                    } else if prop.identifier == ITEM_DEFINITION_INDEX {
+                        if let SendPropValue::Integer(index) = prop.value {
+                            medigun.item_definition_index = Some(index as u32);
+                        }
+                    }
+                }
+            }
+            for id in &message.removed_entities {
+                self.mediguns.remove(id);
+            }
+        }
+    }
+
+    fn on_tick(&mut self, tick: u32, state: &GameState, records: &mut Vec<StreamRecord>) {
+        for medigun in self.mediguns.values_mut() {
+            let current = (medigun.charge, medigun.popped);
+            if medigun.written == Some(current) {
+                continue;
+            }
+            let was_popped = medigun.written.is_some_and(|(_, popped)| popped);
+            medigun.written = Some(current);
+            let record = |kind| {
+                StreamRecord::Ubercharge(Ubercharge {
+                    tick,
+                    kind,
+                    medic_steamid: medigun
+                        .owner
+                        .and_then(|owner| player_by_entity(state, owner))
+                        .and_then(steam_id),
+                    charge_pct: medigun.charge * 100.0,
+                    is_popped: medigun.popped,
+                    uber_type: UberType::from_item_definition(medigun.item_definition_index),
+                })
+            };
+            if medigun.popped && !was_popped {
+                records.push(record(UberEvent::Deployed));
+            }
+            records.push(record(UberEvent::Charge));
+        }
+    }
+}
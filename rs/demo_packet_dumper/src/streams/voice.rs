@@ -0,0 +1,108 @@
+use super::{messages, player_by_entity, steam_id, Extractor, StreamRecord};
+use schemars::JsonSchema;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tf_demo_parser::demo::{
+    message::{packetentities::EntityId, Message},
+    packet::Packet,
+    parser::gamestateanalyser::GameState,
+};
+
+/// A player starting or stopping voice transmission.
+#[derive(Debug, Clone, Serialize, JsonSchema)]
+pub struct VoiceActivity {
+    /// For a stop, the tick the silence was noticed at, which keeps the stream in tick order.
+    pub tick: u32,
+    pub steamid: Option<String>,
+    pub is_talking: bool,
+    /// The last tick the player sent voice data on, for a stop.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_voice_tick: Option<u32>,
+}
+
+/// How long a client can go without sending voice data before they count as having stopped.
+///
+/// Voice is sent in bursts every few ticks while talking, this bridges the gaps between them.
+const SILENCE_TICKS: u32 = 33;
+
+struct Talking {
+    steamid: Option<String>,
+    last_tick: u32,
+}
+
+/// Turns the stream of voice data packets into talking intervals, without decoding the audio.
+///
+/// A player stops talking once they've been silent for `SILENCE_TICKS`, or when the demo ends.
+#[derive(Default)]
+pub(super) struct VoiceExtractor {
+    /// By the client slot the voice data was sent from.
+    talking: BTreeMap<u8, Talking>,
+}
+
+impl VoiceExtractor {
+    fn stop(&mut self, client: u8, tick: u32, records: &mut Vec<StreamRecord>) {
+        if let Some(talking) = self.talking.remove(&client) {
+            records.push(StreamRecord::Voice(VoiceActivity {
+                tick,
+                steamid: talking.steamid,
+                is_talking: false,
+                last_voice_tick: Some(talking.last_tick),
+            }));
+        }
+    }
+}
+
+impl Extractor for VoiceExtractor {
+    fn on_packet(
+        &mut self,
+        tick: u32,
+        packet: &Packet<'_>,
+        state: &GameState,
+        records: &mut Vec<StreamRecord>,
+    ) {
+        for message in messages(packet) {
+            let Message::VoiceData(voice) = message else {
+                continue;
+            };
+            if let Some(talking) = self.talking.get_mut(&voice.client) {
+                talking.last_tick = tick;
+                continue;
+            }
+            // Client slots are one below the entity index of the player
+            let entity = EntityId::from(u32::from(voice.client) + 1);
+            let steamid = player_by_entity(state, entity).and_then(steam_id);
+            records.push(StreamRecord::Voice(VoiceActivity {
+                tick,
+                steamid: steamid.clone(),
+                is_talking: true,
+                last_voice_tick: None,
+            }));
+            self.talking.insert(
+                voice.client,
+                Talking {
+                    steamid,
+                    last_tick: tick,
+                },
+            );
+        }
+    }
+
+    fn on_tick(&mut self, tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        let silent: Vec<u8> = self
+            .talking
+            .iter()
+            .filter(|(_, talking)| tick.saturating_sub(talking.last_tick) > SILENCE_TICKS)
+            .map(|(&client, _)| client)
+            .collect();
+        for client in silent {
+            self.stop(client, tick, records);
+        }
+    }
+
+    fn finish(&mut self, tick: u32, _state: &GameState, records: &mut Vec<StreamRecord>) {
+        let clients: Vec<u8> = self.talking.keys().copied().collect();
+        for client in clients {
+            self.stop(client, tick, records);
+        }
+    }
+}
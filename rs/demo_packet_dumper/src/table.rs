@@ -0,0 +1,43 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use tf_demo_parser::demo::parser::gamestateanalyser::{Class, GameState, PlayerState, Team};
+
+/// One player at one tick, the flat projection of the GameState written by `--format csv`.
+#[derive(Debug, Serialize)]
+pub struct PlayerRow {
+    pub tick: u32,
+    pub steamid: Option<String>,
+    pub name: Option<String>,
+    pub team: Team,
+    pub class: Class,
+    pub health: u16,
+    pub x: f32,
+    pub y: f32,
+    pub z: f32,
+    pub alive: bool,
+    pub score: Option<u32>,
+}
+
+/// Flattens a GameState into a row per player. `scores` holds the latest score by SteamID.
+pub fn player_rows<'s>(
+    state: &'s GameState,
+    scores: &'s HashMap<String, u32>,
+) -> impl Iterator<Item = PlayerRow> + 's {
+    let tick: u32 = state.tick.into();
+    state.players.iter().map(move |player| {
+        let steamid = player.info.as_ref().map(|info| info.steam_id.clone());
+        PlayerRow {
+            tick,
+            score: steamid.as_ref().and_then(|steamid| scores.get(steamid).copied()),
+            steamid,
+            name: player.info.as_ref().map(|info| info.name.clone()),
+            team: player.team,
+            class: player.class,
+            health: player.health,
+            x: player.position.x,
+            y: player.position.y,
+            z: player.position.z,
+            alive: player.state == PlayerState::Alive,
+        }
+    })
+}
@@ -0,0 +1,179 @@
+use demo_packet_dumper::streams::{MatchEvent, MatchEventKind, Round};
+use serde::Serialize;
+use tf_demo_parser::demo::header::Header;
+
+/// The tick rate of a standard TF2 server, 1 / 0.015s.
+pub const DEFAULT_TICKRATE: f64 = 200.0 / 3.0;
+
+/// How far apart two tick rates may be before they're considered to disagree.
+const TICKRATE_TOLERANCE: f64 = 0.01;
+
+/// Where the tick rate used for converting ticks to seconds came from.
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TickrateSource {
+    /// Passed with `--tickrate`.
+    Flag,
+    /// The interval per tick the server announced itself with.
+    ServerInfo,
+    /// The header's duration divided by its ticks.
+    Header,
+    /// The demo said nothing usable about it, so the standard rate was assumed.
+    Default,
+}
+
+/// The tick rate the header's duration and tick count work out to, if it has both.
+pub fn header_tickrate(header: &Header) -> Option<f64> {
+    (header.ticks > 0 && header.duration > 0.0)
+        .then(|| f64::from(header.ticks) / f64::from(header.duration))
+}
+
+/// The tick rate a server's interval per tick works out to.
+pub fn server_tickrate(interval_per_tick: f32) -> Option<f64> {
+    (interval_per_tick > 0.0).then(|| 1.0 / f64::from(interval_per_tick))
+}
+
+/// The tick rate ticks are converted to seconds with.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Tickrate {
+    pub tickrate: f64,
+    pub source: TickrateSource,
+}
+
+impl Tickrate {
+    /// Settles on a tick rate, trusting `--tickrate` first, then the server's interval per tick,
+    /// which is exact, over the header, which is only as precise as its rounded duration.
+    pub fn settle(flag: Option<f64>, server: Option<f64>, header: Option<f64>) -> Self {
+        let (tickrate, source) = match (flag, server, header) {
+            (Some(tickrate), _, _) => (tickrate, TickrateSource::Flag),
+            (None, Some(tickrate), _) => (tickrate, TickrateSource::ServerInfo),
+            (None, None, Some(tickrate)) => (tickrate, TickrateSource::Header),
+            (None, None, None) => (DEFAULT_TICKRATE, TickrateSource::Default),
+        };
+        Tickrate { tickrate, source }
+    }
+
+    /// Like `settle`, but warns when the given or assumed rate disagrees with the server's.
+    pub fn resolve(flag: Option<f64>, server: Option<f64>, header: Option<f64>) -> Self {
+        if let Some(server) = server {
+            let assumed = Tickrate::settle(flag, None, header);
+            if (assumed.tickrate - server).abs() > server * TICKRATE_TOLERANCE {
+                tracing::warn!(
+                    "The {} tick rate of {:.2} doesn't match the server's {server:.2}.",
+                    assumed.source.describe(),
+                    assumed.tickrate
+                );
+            }
+        }
+        Tickrate::settle(flag, server, header)
+    }
+}
+
+impl TickrateSource {
+    fn describe(self) -> &'static str {
+        match self {
+            TickrateSource::Flag => "given",
+            TickrateSource::ServerInfo => "server's",
+            TickrateSource::Header => "header's",
+            TickrateSource::Default => "standard",
+        }
+    }
+}
+
+/// The time of a GameState frame, relative to the origin tick.
+#[derive(Debug, Serialize)]
+pub struct FrameTime {
+    pub tick: u32,
+    /// Negative for frames before the origin, e.g. the pre-game when aligned to the round start.
+    pub time_seconds: f64,
+    /// The game was paused, so the frame's time isn't game time.
+    pub paused: bool,
+}
+
+/// Maps the GameState frames to seconds, for syncing them to an external recording.
+#[derive(Debug, Serialize)]
+pub struct Timing {
+    pub tickrate: f64,
+    pub tickrate_source: TickrateSource,
+    /// The tick at 0 seconds: the first tick of the packet stream, or the first round start.
+    pub origin_tick: u32,
+    pub aligned_to_round_start: bool,
+    /// The inclusive tick ranges the game was paused for.
+    pub pauses: Vec<(u32, u32)>,
+    pub frames: Vec<FrameTime>,
+}
+
+/// Collects the frame ticks while parsing, the origin is only known once the demo is done.
+pub struct TimingRecorder {
+    align_to_round_start: bool,
+    first_tick: Option<u32>,
+    round_start: Option<u32>,
+    pauses: Vec<(u32, u32)>,
+    frames: Vec<u32>,
+}
+
+impl TimingRecorder {
+    pub fn new(align_to_round_start: bool) -> Self {
+        TimingRecorder {
+            align_to_round_start,
+            first_tick: None,
+            round_start: None,
+            pauses: Vec::new(),
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn tick(&mut self, tick: u32) {
+        self.first_tick.get_or_insert(tick);
+    }
+
+    pub fn frame(&mut self, tick: u32) {
+        self.frames.push(tick);
+    }
+
+    /// Rounds arrive as they end, so the first complete one seen is the first to start.
+    pub fn round(&mut self, round: &Round) {
+        if self.round_start.is_none() && !round.partial && !round.waiting_for_players {
+            self.round_start = Some(round.start_tick);
+        }
+    }
+
+    pub fn match_event(&mut self, event: &MatchEvent) {
+        if event.kind == MatchEventKind::Pause {
+            self.pauses.push((event.start_tick, event.end_tick.unwrap_or(u32::MAX)));
+        }
+    }
+
+    /// The tick rate is only settled once the server info was seen, which may not be at the start.
+    pub fn finish(self, tickrate: Tickrate) -> Timing {
+        let first_tick = self.first_tick.unwrap_or_default();
+        let origin_tick = match (self.align_to_round_start, self.round_start) {
+            (true, Some(round_start)) => round_start,
+            (true, None) => {
+                tracing::warn!(
+                    "No round started in the demo, the timing starts at its first tick instead."
+                );
+                first_tick
+            }
+            (false, _) => first_tick,
+        };
+        let seconds_per_tick = 1.0 / tickrate.tickrate;
+        let pauses = self.pauses;
+        Timing {
+            tickrate: tickrate.tickrate,
+            tickrate_source: tickrate.source,
+            origin_tick,
+            aligned_to_round_start: self.align_to_round_start && self.round_start.is_some(),
+            frames: self
+                .frames
+                .into_iter()
+                .map(|tick| FrameTime {
+                    tick,
+                    time_seconds: (f64::from(tick) - f64::from(origin_tick)) * seconds_per_tick,
+                    paused: pauses.iter().any(|&(start, end)| (start..=end).contains(&tick)),
+                })
+                .collect(),
+            pauses,
+        }
+    }
+}
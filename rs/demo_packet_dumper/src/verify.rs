@@ -0,0 +1,127 @@
+use crate::output::{Compression, OutputFormat};
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::{self, BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+use tf_demo_parser::demo::parser::gamestateanalyser::GameState;
+
+/// The part of the `FormatHeader` each output starts with that tells it apart from a frame.
+#[derive(Deserialize)]
+struct Header {
+    #[allow(dead_code)]
+    format_version: u32,
+}
+
+/// A JSON document, which holds its header next to the frames.
+#[derive(Deserialize)]
+struct Document {
+    #[allow(dead_code)]
+    header: Header,
+    records: Vec<serde_json::Value>,
+}
+
+/// Reads the GameState output back for `--verify-output`, checking that every record
+/// deserialises as a frame and that none went missing. Rotated outputs are read chunk by chunk.
+pub fn verify_frames(
+    paths: &[PathBuf],
+    format: OutputFormat,
+    compression: Compression,
+    written: u64,
+) -> Result<(), String> {
+    let mut read = 0;
+    for path in paths {
+        let reader = open(path, compression)
+            .map_err(|e| format!("Couldn't reopen {} to verify it: {e}", path.display()))?;
+        read_frames(reader, format, &mut read)
+            .map_err(|e| format!("{} doesn't read back: {e}", path.display()))?;
+    }
+    if read != written {
+        return Err(format!(
+            "Verifying the output read back {read} frames, but {written} were written."
+        ));
+    }
+    tracing::info!("Verified all {read} frames read back.");
+    Ok(())
+}
+
+fn open(path: &Path, compression: Compression) -> io::Result<Box<dyn BufRead>> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(match compression {
+        Compression::None => Box::new(file),
+        Compression::Gzip => Box::new(BufReader::new(flate2::bufread::MultiGzDecoder::new(file))),
+        Compression::Zstd => Box::new(BufReader::new(zstd::Decoder::with_buffer(file)?)),
+    })
+}
+
+/// Whether there's nothing left to read, as the binary formats don't mark their end.
+fn at_end(reader: &mut impl BufRead) -> Result<bool, String> {
+    reader
+        .fill_buf()
+        .map(|buf| buf.is_empty())
+        .map_err(|e| e.to_string())
+}
+
+/// Reads one output's header and frames, counting the frames into `read` so errors point at
+/// the frame's index across every chunk. An output without frames is empty, header and all.
+fn read_frames(
+    mut reader: Box<dyn BufRead>,
+    format: OutputFormat,
+    read: &mut u64,
+) -> Result<(), String> {
+    let frame_error = |index: u64, e: &dyn std::fmt::Display| format!("frame {index}: {e}");
+    if at_end(&mut reader)? {
+        return Ok(());
+    }
+    match format {
+        OutputFormat::Msgpack => {
+            let mut deserializer = rmp_serde::Deserializer::new(&mut reader);
+            Header::deserialize(&mut deserializer).map_err(|e| format!("header: {e}"))?;
+            while !at_end(deserializer.get_mut())? {
+                GameState::deserialize(&mut deserializer).map_err(|e| frame_error(*read, &e))?;
+                *read += 1;
+            }
+        }
+        OutputFormat::Cbor => {
+            // The header is tagged, which reads fine as a plain value
+            ciborium::from_reader::<ciborium::Value, _>(&mut reader)
+                .map_err(|e| format!("header: {e}"))?;
+            while !at_end(&mut reader)? {
+                ciborium::from_reader::<GameState, _>(&mut reader)
+                    .map_err(|e| frame_error(*read, &e))?;
+                *read += 1;
+            }
+        }
+        OutputFormat::Jsonl => {
+            let mut lines = reader.lines();
+            let header = lines
+                .next()
+                .ok_or("it's empty")?
+                .map_err(|e| e.to_string())?;
+            serde_json::from_str::<Header>(&header).map_err(|e| format!("header: {e}"))?;
+            for line in lines {
+                let line = line.map_err(|e| frame_error(*read, &e))?;
+                serde_json::from_str::<GameState>(&line).map_err(|e| frame_error(*read, &e))?;
+                *read += 1;
+            }
+        }
+        OutputFormat::Json => {
+            let document: serde_json::Value =
+                serde_json::from_reader(reader).map_err(|e| e.to_string())?;
+            // Nothing was written, not even the header
+            if document.as_array().is_some_and(Vec::is_empty) {
+                return Ok(());
+            }
+            let document: Document =
+                serde_json::from_value(document).map_err(|e| format!("header: {e}"))?;
+            for record in document.records {
+                serde_json::from_value::<GameState>(record).map_err(|e| frame_error(*read, &e))?;
+                *read += 1;
+            }
+        }
+        // Rows are a projection, there's no frame to read them back into
+        OutputFormat::Csv => return Err("CSV output can't be verified".to_string()),
+    }
+    Ok(())
+}
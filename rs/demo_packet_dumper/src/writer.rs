@@ -0,0 +1,171 @@
+use crate::{
+    output::{FormatHeader, FrameWriter, RecordSink, StreamSink},
+    table::PlayerRow,
+};
+use demo_packet_dumper::{
+    streams::{StreamKind, StreamRecord},
+    Error,
+};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::mpsc::{self, SyncSender},
+    thread::{self, JoinHandle},
+};
+use tf_demo_parser::demo::parser::gamestateanalyser::GameState;
+
+/// A record of the GameState delta output.
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum Frame {
+    Full(GameState),
+    /// Only the fields selected with `--fields`. These are maps rather than the positional
+    /// arrays of full frames in msgpack, as most of the positions are gone.
+    Projected(Value),
+    /// Written in `--only-changed` mode, with the paths that changed since the previous frame.
+    Changed {
+        tick: u32,
+        changed: Vec<String>,
+        state: Box<Frame>,
+    },
+    /// One player of a GameState, flattened for `--format csv`.
+    Row(PlayerRow),
+    /// Written first and then every `--keyframe-interval` ticks in `--delta-encode` mode, the
+    /// whole state to start rebuilding from. It's the same value the deltas are diffed against,
+    /// so in msgpack too its structs are maps the delta paths can name fields of.
+    Keyframe { tick: u32, keyframe: Value },
+    /// Written between keyframes in `--delta-encode` mode, with what changed since the previous
+    /// frame.
+    Delta {
+        tick: u32,
+        set: Vec<(String, Value)>,
+        removed: Vec<String>,
+    },
+}
+
+/// How many records may be queued before the parse loop waits on the writer.
+const CHANNEL_CAPACITY: usize = 1024;
+
+enum Record {
+    /// Known once the demo header is read, and written by each output ahead of its first record.
+    Start(FormatHeader),
+    Frame(Box<Frame>),
+    /// Raw packets borrow the demo buffer, so they're encoded before being sent.
+    Raw(Vec<u8>),
+    Stream(StreamRecord),
+}
+
+/// Owns the output writers on a dedicated thread, so parsing isn't held up by disk I/O.
+pub struct WriterThread {
+    sender: SyncSender<Record>,
+    handle: JoinHandle<(Vec<PathBuf>, Result<(), Error>)>,
+}
+
+impl WriterThread {
+    pub fn spawn<S: RecordSink + 'static>(
+        mut gsd_writer: Option<FrameWriter<S>>,
+        mut raw_writer: Option<S>,
+        mut stream_writers: HashMap<StreamKind, StreamSink>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::sync_channel(CHANNEL_CAPACITY);
+        let handle = thread::spawn(move || {
+            let mut header = None;
+            // Started lazily, so an output nothing was written to stays empty
+            let mut gsd_started = false;
+            let mut raw_started = false;
+            let mut streams_started = HashSet::new();
+            let mut result = receiver.into_iter().try_for_each(|record| match record {
+                Record::Start(start) => {
+                    header = Some(start);
+                    Ok(())
+                }
+                Record::Frame(frame) => match gsd_writer.as_mut() {
+                    Some(gsd_writer) => {
+                        start_once(gsd_writer, &mut gsd_started, header.as_ref())?;
+                        gsd_writer.write(&frame)
+                    }
+                    None => Ok(()),
+                },
+                Record::Raw(packet) => match raw_writer.as_mut() {
+                    Some(raw_writer) => {
+                        start_once(raw_writer, &mut raw_started, header.as_ref())?;
+                        raw_writer.write_encoded(&packet)
+                    }
+                    None => Ok(()),
+                },
+                Record::Stream(record) => match stream_writers.get_mut(&record.kind()) {
+                    Some(writer) => {
+                        let mut started = !streams_started.insert(record.kind());
+                        start_once(writer, &mut started, header.as_ref())?;
+                        writer.write(&record)
+                    }
+                    None => Ok(()),
+                },
+            });
+            // The chunks are listed even after a failure, as the ones before it are complete
+            let chunks = gsd_writer.as_ref().map(FrameWriter::chunks).unwrap_or_default();
+            // Every writer is finished even if one fails, so as much output as possible is kept
+            let finished = gsd_writer
+                .map(RecordSink::finish)
+                .into_iter()
+                .chain(raw_writer.map(RecordSink::finish))
+                .chain(stream_writers.into_values().map(RecordSink::finish));
+            for finished in finished {
+                if result.is_ok() {
+                    result = finished;
+                }
+            }
+            (chunks, result)
+        });
+        WriterThread { sender, handle }
+    }
+
+    /// Sets the header the outputs start with, before any record is written.
+    pub fn start(&self, header: FormatHeader) -> Result<(), Error> {
+        self.send(Record::Start(header))
+    }
+
+    pub fn write_frame(&self, frame: Frame) -> Result<(), Error> {
+        self.send(Record::Frame(Box::new(frame)))
+    }
+
+    pub fn write_raw(&self, packet: Vec<u8>) -> Result<(), Error> {
+        self.send(Record::Raw(packet))
+    }
+
+    pub fn write_record(&self, record: StreamRecord) -> Result<(), Error> {
+        self.send(Record::Stream(record))
+    }
+
+    fn send(&self, record: Record) -> Result<(), Error> {
+        // The receiver only hangs up when the writer failed, `finish` surfaces the actual error
+        self.sender
+            .send(record)
+            .map_err(|_| Error::output("output writer stopped unexpectedly"))
+    }
+
+    /// Waits for every queued record to be written, returning the GameState chunks written and
+    /// the first write error.
+    pub fn finish(self) -> (Vec<PathBuf>, Result<(), Error>) {
+        drop(self.sender);
+        self.handle
+            .join()
+            .unwrap_or_else(|_| (Vec::new(), Err(Error::output("output writer panicked"))))
+    }
+}
+
+fn start_once<S: RecordSink>(
+    sink: &mut S,
+    started: &mut bool,
+    header: Option<&FormatHeader>,
+) -> Result<(), Error> {
+    if !*started {
+        *started = true;
+        if let Some(header) = header {
+            sink.start(header)?;
+        }
+    }
+    Ok(())
+}
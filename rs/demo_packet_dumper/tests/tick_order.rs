@@ -0,0 +1,83 @@
+use demo_packet_dumper::{parse_demo_with, Error, ParseEvent, ParseOptions, TickOrder};
+
+/// The first 252 ticks of a real demo, with its stop packet and the header's tick count to match.
+/// Compressed, as it's mostly the signon data.
+const SAMPLE_DEMO: &[u8] = include_bytes!("fixtures/short.dem.zst");
+
+fn sample_demo() -> Vec<u8> {
+    zstd::decode_all(SAMPLE_DEMO).expect("the sample demo doesn't decompress")
+}
+
+/// Parses `data`, returning the ticks of the GameState frames `TickOrder` lets through, and how
+/// many it held back. The frames up to a failure count too.
+fn ordered_ticks(data: &[u8]) -> (Vec<u32>, usize, Result<(), Error>) {
+    let mut order = TickOrder::default();
+    let mut ticks = Vec::new();
+    let mut skipped = 0;
+    let result = parse_demo_with(data, &ParseOptions::default(), |event| {
+        if let ParseEvent::GameState(state) = event {
+            let tick = state.tick.into();
+            match order.advance(tick) {
+                Ok(()) => ticks.push(tick),
+                Err(_) => skipped += 1,
+            }
+        }
+        Ok(())
+    });
+    (ticks, skipped, result.map(drop))
+}
+
+fn assert_strictly_increasing(ticks: &[u32]) {
+    for pair in ticks.windows(2) {
+        assert!(pair[0] < pair[1], "tick {} came after {}", pair[1], pair[0]);
+    }
+}
+
+#[test]
+fn repeated_and_earlier_ticks_are_held_back() {
+    let mut order = TickOrder::default();
+    let kept: Vec<u32> = [1, 2, 3, 3, 2, 4, 4, 7, 5, 8]
+        .into_iter()
+        .filter(|&tick| order.advance(tick).is_ok())
+        .collect();
+    assert_eq!(kept, [1, 2, 3, 4, 7, 8]);
+    assert_eq!(order.advance(6), Err(8));
+}
+
+#[test]
+fn sample_demo_ticks_increase() {
+    let data = sample_demo();
+    let (ticks, _, result) = ordered_ticks(&data);
+    result.unwrap();
+    assert!(!ticks.is_empty());
+    assert_strictly_increasing(&ticks);
+}
+
+/// Splices an earlier stretch of packets back in after later ones, so the ticks go backwards the
+/// way they can after resyncing, and checks none of it makes it past `TickOrder`.
+#[test]
+fn spliced_demo_ticks_increase() {
+    let data = sample_demo();
+    // The packets are framed in whole bytes, so they can be cut apart where they start
+    let mut starts = Vec::new();
+    parse_demo_with(&data, &ParseOptions::default(), |event| {
+        if let ParseEvent::Tick { bit_position, .. } = event {
+            if bit_position % 8 == 0 {
+                starts.push(bit_position / 8);
+            }
+        }
+        Ok(())
+    })
+    .unwrap();
+    assert!(
+        starts.len() > 100,
+        "too few packet starts to splice the demo"
+    );
+    let (rewind_to, rewind_at) = (starts[starts.len() / 2], starts[starts.len() * 3 / 4]);
+    let spliced = [&data[..rewind_at], &data[rewind_to..]].concat();
+
+    // The replayed entity deltas may not apply, losing sync is fine as long as the order held
+    let (ticks, skipped, _) = ordered_ticks(&spliced);
+    assert!(skipped > 0, "the spliced demo didn't go back in tick");
+    assert_strictly_increasing(&ticks);
+}